@@ -1,11 +1,15 @@
+use rocket::response::status::BadRequest;
 use rocket::serde::json::Json;
 use serde::{Deserialize, Serialize};
-use shared::calc::Calc;
+use shared::calc::error::CalcError;
+use shared::calc::number::Backend;
+use shared::calc::{Calc, Format};
 
 #[derive(Serialize, Deserialize)]
 pub struct CalcRequest<'r> {
     input: &'r str,
     infix: Option<bool>,
+    backend: Option<Backend>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -13,26 +17,28 @@ pub struct CalcResponse {
     output: String,
 }
 
-#[get("/", data = "<request>")]
-pub fn get(request: Json<CalcRequest<'_>>) -> Json<Calc> {
-    let calc = if request.infix.unwrap_or(false) {
-        Calc::infix(request.input)
+fn read(request: &CalcRequest<'_>) -> Result<Calc, CalcError> {
+    let mut calc = Calc::new(request.backend.unwrap_or_default());
+    if request.infix.unwrap_or(false) {
+        calc.input(Format::Infix(request.input))?;
     } else {
-        Calc::postfix(request.input)
+        calc.input(Format::Postfix(request.input))?;
     }
-    .unwrap();
-    Json(calc)
+    Ok(calc)
+}
+
+#[get("/", data = "<request>")]
+pub fn get(request: Json<CalcRequest<'_>>) -> Result<Json<Calc>, BadRequest<Json<CalcError>>> {
+    let calc = read(&request).map_err(|e| BadRequest(Json(e)))?;
+    Ok(Json(calc))
 }
 
 #[post("/", data = "<request>")]
-pub fn post(request: Json<CalcRequest<'_>>) -> Json<CalcResponse> {
-    let calc = if request.infix.unwrap_or(false) {
-        Calc::infix(request.input)
-    } else {
-        Calc::postfix(request.input)
-    }
-    .unwrap();
-    Json(CalcResponse {
+pub fn post(
+    request: Json<CalcRequest<'_>>,
+) -> Result<Json<CalcResponse>, BadRequest<Json<CalcError>>> {
+    let calc = read(&request).map_err(|e| BadRequest(Json(e)))?;
+    Ok(Json(CalcResponse {
         output: calc.to_string(),
-    })
+    }))
 }
@@ -1,16 +1,244 @@
+pub mod constant;
+pub mod context;
+pub mod error;
 pub mod expr;
+pub mod function;
+pub mod number;
+pub mod parser;
 pub mod token;
 
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::fmt::{Debug, Display, Formatter};
 
-use crate::calc::expr::Expr;
-use crate::calc::token::Token;
+use crate::calc::context::{
+    Angle, Context, Coordinates, Definition, Definitions, Macros, Policy, Precision, Radix,
+    Registers, Variables, Word,
+};
+use crate::calc::error::{CalcError, ErrorKind, EvalError};
+use crate::calc::expr::{Comparison, Expr};
+use crate::calc::number::{Backend, Value};
+use crate::calc::parser::{Fixity, Operator, Parser, Statement};
+use crate::calc::token::{Span, Token};
 use itertools::join;
+use num::{BigInt, ToPrimitive};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Calc {
     memory: Vec<Expr>,
+    #[serde(default)]
+    backend: Backend,
+    #[serde(default)]
+    precision: Precision,
+    #[serde(default)]
+    angle: Angle,
+    #[serde(default)]
+    coordinates: Coordinates,
+    #[serde(default)]
+    radix: Radix,
+    #[serde(default)]
+    word: Word,
+    #[serde(default)]
+    policy: Policy,
+    #[serde(default)]
+    variables: Variables,
+    #[serde(default)]
+    functions: Definitions,
+    #[serde(default)]
+    registers: Registers,
+    #[serde(default)]
+    journal: Journal,
+    #[serde(default)]
+    macros: Macros,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recording: Option<Recording>,
+    #[serde(default)]
+    steps: Steps,
+    #[serde(skip)]
+    changes: Vec<Change>,
+    /// Macros and programs being run, each by a token of the one running it.
+    #[serde(skip)]
+    nesting: usize,
+    /// Counters of the `for` loops being run, innermost last.
+    #[serde(skip)]
+    locals: Vec<(String, Expr)>,
+}
+
+/// A single change made to the state of a [`Calc`], holding what it replaced so that it can
+/// be reverted, either when rolling back an input one of whose tokens fails or with `undo`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Change {
+    Push,
+    Pop(Expr),
+    /// The whole stack was replaced by `clear`.
+    Clear(Vec<Expr>),
+    /// The entry at the given depth was moved to the top, by `roll`.
+    Roll(usize),
+    /// The top entry was moved down to the given depth, undoing a roll.
+    Unroll(usize),
+    /// The order of the whole stack was reversed.
+    Reverse,
+    Angle(Angle),
+    Coordinates(Coordinates),
+    Radix(Radix),
+    /// A variable was stored, replacing the previous value if any.
+    Store(String, Option<Expr>),
+    /// A function was defined, replacing the previous definition if any.
+    Define(String, Option<Definition>),
+    /// A numbered register was set or cleared, replacing the previous value if any.
+    Register(usize, Option<Expr>),
+    /// A macro was recorded, replacing the previous one if any.
+    Macro(String, Option<Vec<String>>),
+}
+
+/// A macro being recorded, which gets its name from the token following `rec`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Recording {
+    name: Option<String>,
+    tokens: Vec<String>,
+    /// Control structures opened by the tokens, whose `end` is recorded rather than ending
+    /// the macro.
+    #[serde(default)]
+    open: usize,
+}
+
+/// Most macros and programs running one another at once, which stops one running itself
+/// forever.
+const MAX_NESTING: usize = 64;
+
+/// Most tokens of programs, loops and macros a new [`Calc`] runs for a single input.
+const STEP_LIMIT: usize = 100_000;
+
+/// How many tokens of programs the current input has run, which stops those that never end.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Steps {
+    #[serde(skip)]
+    taken: usize,
+    limit: usize,
+}
+
+impl Default for Steps {
+    fn default() -> Self {
+        Steps {
+            taken: 0,
+            limit: STEP_LIMIT,
+        }
+    }
+}
+
+/// Most tokens a new [`Calc`] can undo.
+const UNDO_LEVELS: usize = 100;
+
+/// The changes made by each of the past tokens, which `undo` reverts and `redo` makes again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Journal {
+    /// Changes of the tokens that can be undone, oldest first.
+    done: VecDeque<Vec<Change>>,
+    /// Changes reverting the undone tokens, most recently undone last.
+    undone: Vec<Vec<Change>>,
+    /// Most tokens that can be undone, past which the oldest ones are forgotten.
+    levels: usize,
+    /// Edits made to the journal by the current input, oldest first, so that it can be rolled
+    /// back without keeping a copy of the whole journal.
+    #[serde(skip)]
+    edits: Vec<Edit>,
+}
+
+/// An edit to the [`Journal`], holding whatever it removed so that it can be reverted.
+#[derive(Debug, Clone)]
+enum Edit {
+    /// Changes were added to those that can be undone.
+    Done,
+    /// Changes were added to those that can be redone.
+    Undone,
+    /// The newest changes that could be undone were taken to be undone.
+    Undo(Vec<Change>),
+    /// The newest changes that could be redone were taken to be redone.
+    Redo(Vec<Change>),
+    /// The oldest changes were forgotten past the undo levels.
+    Forget(Vec<Change>),
+    /// The changes that could be redone were dropped by a new token.
+    Clear(Vec<Vec<Change>>),
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Journal {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+            levels: UNDO_LEVELS,
+            edits: Vec::new(),
+        }
+    }
+}
+
+impl Journal {
+    /// Keeps the changes of a token, which leaves nothing to redo.
+    fn record(&mut self, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+        self.done.push_back(changes);
+        self.edits.push(Edit::Done);
+        if !self.undone.is_empty() {
+            self.edits
+                .push(Edit::Clear(std::mem::take(&mut self.undone)));
+        }
+        self.truncate();
+    }
+
+    /// Takes the newest changes that can be undone.
+    fn undo(&mut self) -> Option<Vec<Change>> {
+        let changes = self.done.pop_back()?;
+        self.edits.push(Edit::Undo(changes.clone()));
+        Some(changes)
+    }
+
+    /// Keeps the changes reverting an undone token.
+    fn undone(&mut self, changes: Vec<Change>) {
+        self.undone.push(changes);
+        self.edits.push(Edit::Undone);
+    }
+
+    /// Takes the newest changes that can be redone.
+    fn redo(&mut self) -> Option<Vec<Change>> {
+        let changes = self.undone.pop()?;
+        self.edits.push(Edit::Redo(changes.clone()));
+        Some(changes)
+    }
+
+    /// Keeps the changes making a redone token again, which can be undone once more.
+    fn redone(&mut self, changes: Vec<Change>) {
+        self.done.push_back(changes);
+        self.edits.push(Edit::Done);
+        self.truncate();
+    }
+
+    fn truncate(&mut self) {
+        while self.done.len() > self.levels {
+            if let Some(changes) = self.done.pop_front() {
+                self.edits.push(Edit::Forget(changes));
+            }
+        }
+    }
+
+    /// Reverts the edits made since the current input started, newest first.
+    fn rollback(&mut self) {
+        while let Some(edit) = self.edits.pop() {
+            match edit {
+                Edit::Done => {
+                    self.done.pop_back();
+                }
+                Edit::Undone => {
+                    self.undone.pop();
+                }
+                Edit::Undo(changes) => self.done.push_back(changes),
+                Edit::Redo(changes) => self.undone.push(changes),
+                Edit::Forget(changes) => self.done.push_front(changes),
+                Edit::Clear(undone) => self.undone = undone,
+            }
+        }
+    }
 }
 
 pub enum Format<'a> {
@@ -19,107 +247,983 @@ pub enum Format<'a> {
 }
 
 impl Calc {
-    pub fn postfix(input: &str) -> Result<Calc, String> {
+    /// An empty calculator evaluating with the given numeric backend.
+    pub fn new(backend: Backend) -> Calc {
+        Calc {
+            backend,
+            ..Calc::default()
+        }
+    }
+
+    pub fn postfix(input: &str) -> Result<Calc, CalcError> {
         let mut calc = Calc::default();
         calc.input(Format::Postfix(input))?;
         Ok(calc)
     }
 
-    pub fn infix(input: &str) -> Result<Calc, String> {
+    pub fn infix(input: &str) -> Result<Calc, CalcError> {
         let mut calc = Calc::default();
         calc.input(Format::Infix(input))?;
         Ok(calc)
     }
 
-    pub fn input(&mut self, input: Format) -> Result<(), String> {
-        match input {
-            Format::Infix(input) => {
-                let tokens = Token::shunting_yard(input);
-                for token in tokens {
-                    self.parse_token(token)?;
+    /// Reads all tokens of the input, either applying every one of them or, if any fails,
+    /// leaving the calculator exactly as it was before the call.
+    pub fn input(&mut self, input: Format) -> Result<(), CalcError> {
+        let recording = self.recording.clone();
+        self.steps.taken = 0;
+        let result = match input {
+            Format::Infix(input) => self.read_infix(input),
+            Format::Postfix(input) => self.read_postfix(input),
+        };
+        if result.is_err() {
+            self.rollback();
+            self.journal.rollback();
+            self.recording = recording;
+            self.locals.clear();
+        }
+        self.journal.edits.clear();
+        self.changes.clear();
+        result
+    }
+
+    fn read_infix(&mut self, input: &str) -> Result<(), CalcError> {
+        let start = self.changes.len();
+        let statement = Parser::parse(input, &self.context())?;
+        let end = matches!(statement, None | Some(Statement::Command(Token::End, _)));
+        if self.recording.is_some() && !end {
+            let span = Span::new(0, input.len());
+            return Err(CalcError::new(ErrorKind::RecordingInfix, input, span));
+        }
+        match statement {
+            Some(Statement::Expr(expr)) => self.push(expr),
+            Some(Statement::Assign(name, expr)) => self.store(name, expr),
+            Some(Statement::Define(name, definition)) => self.define(name, definition),
+            Some(Statement::Command(token, span)) => {
+                return self
+                    .read_token(token, &input[span.start..span.end])
+                    .map_err(|kind| CalcError::new(kind, input, span));
+            }
+            None => {}
+        }
+        self.record(start);
+        Ok(())
+    }
+
+    /// Reads the input a token or a whole control structure at a time, each being a single
+    /// entry of the journal.
+    fn read_postfix(&mut self, input: &str) -> Result<(), CalcError> {
+        let error = |(kind, span)| CalcError::new(kind, input, span);
+        let tokens = Token::split(input);
+        let mut rest = tokens.as_slice();
+        while let Some(((token, span), tail)) = rest.split_first() {
+            if self.recording.is_some() {
+                self.read_token(token.clone(), &input[span.start..span.end])
+                    .map_err(|kind| error((kind, *span)))?;
+                rest = tail;
+                continue;
+            }
+            let (block, tail) = rest.split_at(structure(rest).map_err(error)?);
+            match block {
+                [(token, span)] => {
+                    self.parse_token(token.clone())
+                        .map_err(|kind| error((kind, *span)))?;
+                }
+                block => {
+                    let start = self.changes.len();
+                    self.run_structure(input, block).map_err(error)?;
+                    self.record(start);
+                }
+            }
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Applies a postfix token or, while a macro is being recorded, keeps its text instead.
+    fn read_token(&mut self, token: Token, text: &str) -> Result<(), ErrorKind> {
+        let Some(recording) = &mut self.recording else {
+            return self.parse_token(token);
+        };
+        match (token, &recording.name) {
+            (Token::Identifier(name), None) => recording.name = Some(name),
+            (_, None) => return Err(ErrorKind::ExpectedName),
+            (Token::End, Some(_)) if recording.open == 0 => return self.parse_token(Token::End),
+            (Token::Record, Some(_)) => return Err(ErrorKind::AlreadyRecording),
+            (Token::Unknown(_), Some(_)) => return Err(ErrorKind::UnknownToken),
+            (token, Some(_)) => {
+                match token {
+                    Token::If | Token::While => recording.open += 1,
+                    Token::End => recording.open -= 1,
+                    _ => {}
+                }
+                recording.tokens.push(String::from(text));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs postfix tokens on behalf of a control structure, a macro or a program, failing
+    /// with the span of the token at fault.
+    fn run(&mut self, input: &str, tokens: &[(Token, Span)]) -> Result<(), (ErrorKind, Span)> {
+        let mut rest = tokens;
+        while let Some((_, span)) = rest.first() {
+            self.step().map_err(|kind| (kind, *span))?;
+            let (block, tail) = rest.split_at(structure(rest)?);
+            match block {
+                [(Token::Undo | Token::Redo | Token::Record, span)] => {
+                    return Err((ErrorKind::UnexpectedToken, *span));
                 }
+                [(token, span)] => {
+                    self.apply_token(token.clone())
+                        .map_err(|kind| (kind, *span))?;
+                }
+                block => self.run_structure(input, block)?,
             }
-            Format::Postfix(input) => {
-                for token in input.split_ascii_whitespace() {
-                    let token = Token::new(token);
-                    self.parse_token(token)?;
+            rest = tail;
+        }
+        Ok(())
+    }
+
+    /// Runs a control structure, from its opening token to its closing one.
+    fn run_structure(
+        &mut self,
+        input: &str,
+        block: &[(Token, Span)],
+    ) -> Result<(), (ErrorKind, Span)> {
+        let (opening, span) = &block[0];
+        let inner = &block[1..block.len() - 1];
+        let incomplete = (ErrorKind::IncompleteStructure, *span);
+        let error = |kind| (kind, *span);
+        match opening {
+            Token::If => {
+                let (condition, branches) = split(inner, &Token::Then).ok_or(incomplete)?;
+                let (then, otherwise) = split(branches, &Token::Else).unwrap_or((branches, &[]));
+                self.run(input, condition)?;
+                match self.condition().map_err(error)? {
+                    true => self.run(input, then),
+                    false => self.run(input, otherwise),
                 }
             }
+            Token::While => {
+                let (condition, body) = split(inner, &Token::Repeat).ok_or(incomplete)?;
+                loop {
+                    self.step().map_err(error)?;
+                    self.run(input, condition)?;
+                    if !self.condition().map_err(error)? {
+                        return Ok(());
+                    }
+                    self.run(input, body)?;
+                }
+            }
+            Token::For => {
+                let [(Token::Identifier(name) | Token::Name(name), _), body @ ..] = inner else {
+                    return Err(incomplete);
+                };
+                let (mut counter, last) = self.bounds().map_err(error)?;
+                let one = self.backend.from_integer(BigInt::from(1));
+                let invalid = (ErrorKind::InvalidBounds, *span);
+                let holds = |x: &Value| x.compare(Comparison::LessOrEqual, &last).ok();
+                while !holds(&counter).ok_or(invalid)?.is_zero() {
+                    self.step().map_err(error)?;
+                    self.locals
+                        .push((name.clone(), Expr::Number(counter.clone())));
+                    let result = self.run(input, body);
+                    self.locals.pop();
+                    result?;
+                    counter = counter.add(&one).map_err(|_| invalid)?;
+                }
+                Ok(())
+            }
+            Token::ProgramOpen => {
+                let tokens = inner.iter().map(|(_, span)| &input[span.start..span.end]);
+                self.push(Expr::Program(join(tokens, " ")));
+                Ok(())
+            }
+            _ => Err((ErrorKind::UnexpectedToken, *span)),
+        }
+    }
+
+    /// Counts a token run by a program, failing past the step limit.
+    fn step(&mut self) -> Result<(), ErrorKind> {
+        self.steps.taken += 1;
+        if self.steps.taken > self.steps.limit {
+            return Err(ErrorKind::StepLimit);
         }
         Ok(())
     }
 
-    pub fn eval(&self) -> Vec<f64> {
-        self.memory.iter().map(|e| e.eval()).collect()
+    /// Pops the condition of a control structure, which holds unless it is zero.
+    fn condition(&mut self) -> Result<bool, ErrorKind> {
+        let x = self.pop().ok_or(ErrorKind::MissingOperand)?;
+        let value = x.try_eval_in(&self.context()).ok();
+        let value = value.filter(|x| !x.is_nan());
+        Ok(!value.ok_or(ErrorKind::InvalidCondition)?.is_zero())
+    }
+
+    /// Pops the first and last values of the counter of a `for` loop.
+    fn bounds(&mut self) -> Result<(Value, Value), ErrorKind> {
+        let last = self.pop().ok_or(ErrorKind::MissingOperands)?;
+        let first = self.pop().ok_or(ErrorKind::MissingOperand)?;
+        let context = self.context();
+        let value = |x: Expr| {
+            let value = x.try_eval_in(&context).ok();
+            value.filter(|x| x.is_finite() && !matches!(x, Value::Complex(z) if z.im != 0.0))
+        };
+        match (value(first), value(last)) {
+            (Some(first), Some(last)) => Ok((first, last)),
+            _ => Err(ErrorKind::InvalidBounds),
+        }
+    }
+
+    /// The value of the counter of the innermost `for` loop with the given name, if any.
+    fn local(&self, name: &str) -> Option<&Expr> {
+        let mut locals = self.locals.iter().rev();
+        locals
+            .find(|(local, _)| local == name)
+            .map(|(_, value)| value)
+    }
+
+    pub fn eval(&self) -> Vec<Value> {
+        let context = self.context();
+        self.memory.iter().map(|e| e.eval_in(&context)).collect()
+    }
+
+    /// Evaluates every expression of the stack, failing on the first one whose value the
+    /// policy does not accept.
+    pub fn try_eval(&self) -> Result<Vec<Value>, EvalError> {
+        let context = self.context();
+        self.memory
+            .iter()
+            .map(|e| e.try_eval_in(&context))
+            .collect()
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.precision
+    }
+
+    /// Sets the digits decimal divisions and square roots are rounded to, and how.
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.precision = precision;
+    }
+
+    pub fn angle(&self) -> Angle {
+        self.angle
+    }
+
+    pub fn coordinates(&self) -> Coordinates {
+        self.coordinates
+    }
+
+    pub fn radix(&self) -> Radix {
+        self.radix
+    }
+
+    pub fn word(&self) -> Word {
+        self.word
+    }
+
+    /// Sets the size of the words programmer mode wraps integers around, and whether they
+    /// are signed.
+    pub fn set_word(&mut self, word: Word) {
+        self.word = word;
+    }
+
+    /// Writes a value the way the settings say, with integers in the radix set with `hex`,
+    /// `oct`, `bin` or `dec`, complex numbers in the coordinates set with `rect` or `polar`
+    /// and fractions as mixed numbers.
+    pub fn format(&self, value: &Value) -> String {
+        if let Some(text) = self.to_radix(value) {
+            return text;
+        }
+        match (self.coordinates, value) {
+            (Coordinates::Polar, Value::Complex(_)) => value.to_polar(self.angle),
+            _ => format!("{:#}", value),
+        }
+    }
+
+    /// Most tokens `undo` can revert.
+    pub fn undo_levels(&self) -> usize {
+        self.journal.levels
+    }
+
+    /// Sets how many tokens `undo` can revert, forgetting the oldest ones past that.
+    pub fn set_undo_levels(&mut self, levels: usize) {
+        self.journal.levels = levels;
+        self.journal.truncate();
+        self.journal.edits.clear();
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: Policy) {
+        self.policy = policy;
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    /// Functions defined with `def`, by name.
+    pub fn functions(&self) -> &Definitions {
+        &self.functions
+    }
+
+    /// Values stored in numbered registers, by number.
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Most tokens of programs, loops and macros an input may run.
+    pub fn step_limit(&self) -> usize {
+        self.steps.limit
+    }
+
+    /// Sets how many tokens of programs, loops and macros an input may run before failing,
+    /// so that one which never ends does not hang.
+    pub fn set_step_limit(&mut self, limit: usize) {
+        self.steps.limit = limit;
+    }
+
+    /// Macros recorded with `rec name ... end`, by name.
+    pub fn macros(&self) -> &Macros {
+        &self.macros
+    }
+
+    /// Whether the tokens typed are being recorded into a macro rather than applied.
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Deletes a macro, giving back its tokens if there was one.
+    pub fn remove_macro(&mut self, name: &str) -> Option<Vec<String>> {
+        self.macros.remove(name)
+    }
+
+    /// Writes the macros as postfix input recording them again, one per line.
+    pub fn export_macros(&self) -> String {
+        let lines = self.macros.iter().map(|(name, tokens)| {
+            let tokens = tokens.iter().map(String::as_str);
+            join(["rec", name].into_iter().chain(tokens).chain(["end"]), " ")
+        });
+        join(lines, "\n")
+    }
+
+    /// Evaluates an expression with the settings of the calculator, like one held by a
+    /// register or a variable.
+    pub fn value(&self, expr: &Expr) -> Value {
+        expr.eval_in(&self.context())
+    }
+
+    fn context(&self) -> Context<'_> {
+        Context {
+            backend: self.backend,
+            precision: self.precision,
+            angle: self.angle,
+            policy: self.policy,
+            word: self.word,
+            variables: &self.variables,
+            definitions: &self.functions,
+            ..Context::default()
+        }
     }
 
     pub fn is_empty(&self) -> bool {
         self.memory.is_empty()
     }
 
-    fn parse_token(&mut self, token: Token) -> Result<(), String> {
-        match token {
-            Token::Plus => {
-                let y = self.memory.pop().ok_or("Missing operands")?;
-                let x = self.memory.pop().ok_or("Missing operand")?;
-                let e = Expr::Add(Box::from(x), Box::from(y));
-                self.memory.push(e);
-            }
-            Token::Minus => {
-                let y = self.memory.pop().ok_or("Missing operands")?;
-                let x = self.memory.pop().ok_or("Missing operand")?;
-                let e = Expr::Subtract(Box::from(x), Box::from(y));
-                self.memory.push(e);
-            }
-            Token::Slash => {
-                let y = self.memory.pop().ok_or("Missing operands")?;
-                let x = self.memory.pop().ok_or("Missing operand")?;
-                let e = Expr::Divide(Box::from(x), Box::from(y));
-                self.memory.push(e);
-            }
-            Token::Star => {
-                let y = self.memory.pop().ok_or("Missing operands")?;
-                let x = self.memory.pop().ok_or("Missing operand")?;
-                let e = Expr::Multiply(Box::from(x), Box::from(y));
-                self.memory.push(e);
-            }
-            Token::Caret => {
-                let y = self.memory.pop().ok_or("Missing operands")?;
-                let x = self.memory.pop().ok_or("Missing operand")?;
-                let e = Expr::Power(Box::from(x), Box::from(y));
-                self.memory.push(e);
-            }
-            Token::Sqrt => {
-                let x = self.memory.pop().ok_or("Missing operand")?;
-                let e = Expr::Sqrt(Box::from(x));
-                self.memory.push(e);
+    fn push(&mut self, expr: Expr) {
+        self.memory.push(expr);
+        self.changes.push(Change::Push);
+    }
+
+    fn pop(&mut self) -> Option<Expr> {
+        let expr = self.memory.pop()?;
+        self.changes.push(Change::Pop(expr.clone()));
+        Some(expr)
+    }
+
+    fn clear(&mut self) {
+        self.replace(Vec::new());
+    }
+
+    fn replace(&mut self, memory: Vec<Expr>) {
+        let previous = std::mem::replace(&mut self.memory, memory);
+        self.changes.push(Change::Clear(previous));
+    }
+
+    /// Rearranges the stack with a stack command, with the fewest pushes and pops it takes.
+    /// Commands with a count, like `3 pick`, take it from the top of the stack.
+    fn shuffle(&mut self, command: &Token) -> Result<(), ErrorKind> {
+        let count = match command {
+            Token::Roll | Token::Pick | Token::DropN | Token::DupN => self.count()?,
+            _ => 0,
+        };
+        let depth = self.memory.len();
+        match command {
+            Token::Swap => {
+                let mut operands = self.take(2)?;
+                operands.swap(0, 1);
+                operands.into_iter().for_each(|x| self.push(x));
             }
-            Token::Undo => {
-                let x = self.memory.pop().ok_or("Nothing to undo")?;
-                for expr in x.undo() {
-                    self.memory.push(expr.clone());
+            Token::Dup => {
+                operands(&self.memory, 1)?;
+                self.push(self.memory[depth - 1].clone());
+            }
+            Token::Over => {
+                operands(&self.memory, 2)?;
+                self.push(self.memory[depth - 2].clone());
+            }
+            Token::Rot => {
+                let mut operands = self.take(3)?;
+                operands.rotate_left(1);
+                operands.into_iter().for_each(|x| self.push(x));
+            }
+            // Rolling and reversing are recorded as such, rather than as every entry they move
+            Token::Roll if count > 0 => {
+                operands(&self.memory, count)?;
+                self.roll(count);
+            }
+            Token::Pick if count > 0 => {
+                operands(&self.memory, count)?;
+                self.push(self.memory[depth - count].clone());
+            }
+            Token::Pick => return Err(ErrorKind::InvalidCount),
+            Token::DropN => {
+                operands(&self.memory, count)?;
+                for _ in 0..count {
+                    self.pop();
+                }
+            }
+            Token::DupN => {
+                operands(&self.memory, count)?;
+                for i in depth - count..depth {
+                    self.push(self.memory[i].clone());
+                }
+            }
+            Token::Depth => {
+                let depth = self.backend.from_integer(BigInt::from(depth));
+                self.push(Expr::Number(depth));
+            }
+            Token::Reverse => {
+                self.memory.reverse();
+                self.changes.push(Change::Reverse);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Moves the entry at the given depth, counted from 1 at the top, to the top.
+    fn roll(&mut self, depth: usize) {
+        let expr = self.memory.remove(self.memory.len() - depth);
+        self.memory.push(expr);
+        self.changes.push(Change::Roll(depth));
+    }
+
+    /// Pops the operands of a stack command, deepest first.
+    fn take(&mut self, count: usize) -> Result<Vec<Expr>, ErrorKind> {
+        operands(&self.memory, count)?;
+        let mut operands: Vec<Expr> = (0..count).filter_map(|_| self.pop()).collect();
+        operands.reverse();
+        Ok(operands)
+    }
+
+    /// Pops the count of a stack command, as a natural number.
+    fn count(&mut self) -> Result<usize, ErrorKind> {
+        let x = self.pop().ok_or(ErrorKind::MissingOperand)?;
+        let count = x.try_eval_in(&self.context()).ok();
+        let count = count.and_then(|n| n.to_integer()?.to_usize());
+        count.ok_or(ErrorKind::InvalidCount)
+    }
+
+    fn set_angle(&mut self, angle: Angle) {
+        let previous = std::mem::replace(&mut self.angle, angle);
+        self.changes.push(Change::Angle(previous));
+    }
+
+    /// Writes an integer in the radix setting, as the unsigned word with the same bits in
+    /// programmer mode, so that -1 is `0xFF` with 8 bit words.
+    fn to_radix(&self, value: &Value) -> Option<String> {
+        if self.radix == Radix::Dec {
+            return None;
+        }
+        let n = value.to_integer()?;
+        let n = match self.backend {
+            Backend::Programmer => self.word.unsigned(&n),
+            _ => n,
+        };
+        Some(self.radix.format(&n))
+    }
+
+    fn set_radix(&mut self, radix: Radix) {
+        let previous = std::mem::replace(&mut self.radix, radix);
+        self.changes.push(Change::Radix(previous));
+    }
+
+    fn set_coordinates(&mut self, coordinates: Coordinates) {
+        let previous = std::mem::replace(&mut self.coordinates, coordinates);
+        self.changes.push(Change::Coordinates(previous));
+    }
+
+    /// Stores a value under a name, resolving the variables it refers to so that `x = x + 1`
+    /// increments x.
+    fn store(&mut self, name: String, expr: Expr) {
+        let expr = expr.resolve(&self.variables);
+        let previous = self.variables.insert(name.clone(), expr);
+        self.changes.push(Change::Store(name, previous));
+    }
+
+    /// Sets or clears a numbered register, resolving the variables its value refers to as
+    /// [`Calc::store`] does.
+    fn set_register(&mut self, register: usize, expr: Option<Expr>) {
+        let previous = match expr {
+            Some(expr) => {
+                let expr = expr.resolve(&self.variables);
+                self.registers.insert(register, expr)
+            }
+            None => self.registers.remove(&register),
+        };
+        self.changes.push(Change::Register(register, previous));
+    }
+
+    /// The number of the register an expression names, if it is a number at all.
+    fn register(&self, expr: &Expr) -> Option<Result<usize, ErrorKind>> {
+        let Expr::Number(n) = expr else {
+            return None;
+        };
+        let register = n.to_integer().and_then(|n| n.to_usize());
+        Some(register.ok_or(ErrorKind::InvalidRegister))
+    }
+
+    fn define(&mut self, name: String, definition: Definition) {
+        let previous = self.functions.insert(name.clone(), definition);
+        self.changes.push(Change::Define(name, previous));
+    }
+
+    /// The tokens of the program a variable holds, if it holds one.
+    fn program(&self, name: &str) -> Option<String> {
+        match self.variables.get(name) {
+            Some(Expr::Program(tokens)) => Some(tokens.clone()),
+            _ => None,
+        }
+    }
+
+    fn set_macro(&mut self, name: String, tokens: Vec<String>) {
+        let previous = self.macros.insert(name.clone(), tokens);
+        self.changes.push(Change::Macro(name, previous));
+    }
+
+    /// Runs the tokens of a macro or a program as if they had been typed, as a single token
+    /// of the journal.
+    fn play(&mut self, tokens: &str) -> Result<(), ErrorKind> {
+        if self.nesting == MAX_NESTING {
+            return Err(ErrorKind::Nesting);
+        }
+        self.nesting += 1;
+        let result = self.run(tokens, &Token::split(tokens));
+        self.nesting -= 1;
+        result.map_err(|(kind, _)| kind)
+    }
+
+    /// Resolves a name to the counter of a loop, a macro, a function, a variable or a named
+    /// number, in that order: counters of loops hide everything else, and macros the
+    /// functions and variables. Variables holding programs run them.
+    fn identifier(&mut self, name: String) -> Result<(), ErrorKind> {
+        if let Some(value) = self.local(&name).cloned() {
+            self.push(value);
+        } else if let Some(tokens) = self.macros.get(&name) {
+            self.play(&join(tokens, " "))?;
+        } else if let Some(definition) = self.functions.get(&name) {
+            let count = definition.params.len();
+            if self.memory.len() < count {
+                return Err(ErrorKind::MissingOperands);
+            }
+            let mut args: Vec<Expr> = (0..count).filter_map(|_| self.pop()).collect();
+            args.reverse();
+            self.push(Expr::Call(name, args));
+        } else if let Some(tokens) = self.program(&name) {
+            self.play(&tokens)?;
+        } else if self.variables.contains_key(&name) {
+            self.push(Expr::Variable(name));
+        } else {
+            let value = Value::named(&name).ok_or(ErrorKind::UndefinedVariable)?;
+            self.push(Expr::Number(value));
+        }
+        Ok(())
+    }
+
+    /// Reverts the changes made since the current input started, newest first.
+    fn rollback(&mut self) {
+        while let Some(change) = self.changes.pop() {
+            self.revert(change);
+        }
+    }
+
+    /// Reverts a change, giving back the change which reverts it in turn.
+    fn revert(&mut self, change: Change) -> Change {
+        match change {
+            Change::Push => match self.memory.pop() {
+                Some(expr) => Change::Pop(expr),
+                None => Change::Clear(Vec::new()),
+            },
+            Change::Pop(expr) => {
+                self.memory.push(expr);
+                Change::Push
+            }
+            Change::Clear(memory) => Change::Clear(std::mem::replace(&mut self.memory, memory)),
+            Change::Roll(depth) => {
+                if let Some(expr) = self.memory.pop() {
+                    self.memory.insert(self.memory.len() + 1 - depth, expr);
                 }
+                Change::Unroll(depth)
+            }
+            Change::Unroll(depth) => {
+                let expr = self.memory.remove(self.memory.len() - depth);
+                self.memory.push(expr);
+                Change::Roll(depth)
+            }
+            Change::Reverse => {
+                self.memory.reverse();
+                Change::Reverse
+            }
+            Change::Angle(angle) => Change::Angle(std::mem::replace(&mut self.angle, angle)),
+            Change::Coordinates(coordinates) => {
+                Change::Coordinates(std::mem::replace(&mut self.coordinates, coordinates))
+            }
+            Change::Radix(radix) => Change::Radix(std::mem::replace(&mut self.radix, radix)),
+            Change::Store(name, Some(expr)) => {
+                let previous = self.variables.insert(name.clone(), expr);
+                Change::Store(name, previous)
+            }
+            Change::Store(name, None) => {
+                let previous = self.variables.remove(&name);
+                Change::Store(name, previous)
+            }
+            Change::Define(name, Some(definition)) => {
+                let previous = self.functions.insert(name.clone(), definition);
+                Change::Define(name, previous)
+            }
+            Change::Define(name, None) => {
+                let previous = self.functions.remove(&name);
+                Change::Define(name, previous)
+            }
+            Change::Register(register, Some(expr)) => {
+                let previous = self.registers.insert(register, expr);
+                Change::Register(register, previous)
+            }
+            Change::Register(register, None) => {
+                let previous = self.registers.remove(&register);
+                Change::Register(register, previous)
+            }
+            Change::Macro(name, Some(tokens)) => {
+                let previous = self.macros.insert(name.clone(), tokens);
+                Change::Macro(name, previous)
+            }
+            Change::Macro(name, None) => {
+                let previous = self.macros.remove(&name);
+                Change::Macro(name, previous)
+            }
+        }
+    }
+
+    /// Keeps the changes made since the given one in the journal, as those of a single token.
+    fn record(&mut self, start: usize) {
+        let changes = self.changes[start..].to_vec();
+        self.journal.record(changes);
+    }
+
+    /// Reverts the changes of a token newest first, giving back those which make them again.
+    /// These are part of the current input, so that it can still be rolled back.
+    fn replay(&mut self, changes: Vec<Change>) -> Vec<Change> {
+        let mut reverse = Vec::with_capacity(changes.len());
+        for change in changes.into_iter().rev() {
+            let change = self.revert(change);
+            self.changes.push(change.clone());
+            reverse.push(change);
+        }
+        reverse
+    }
+
+    /// Reads a token, recording its changes in the journal unless it moves through it.
+    fn parse_token(&mut self, token: Token) -> Result<(), ErrorKind> {
+        match token {
+            Token::Undo => {
+                let changes = self.journal.undo().ok_or(ErrorKind::NothingToUndo)?;
+                let changes = self.replay(changes);
+                self.journal.undone(changes);
+            }
+            Token::Redo => {
+                let changes = self.journal.redo().ok_or(ErrorKind::NothingToRedo)?;
+                let changes = self.replay(changes);
+                self.journal.redone(changes);
+            }
+            token => {
+                let start = self.changes.len();
+                self.apply_token(token)?;
+                self.record(start);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_token(&mut self, token: Token) -> Result<(), ErrorKind> {
+        match token {
+            Token::Number(literal) => {
+                let value = self.backend.parse(&literal);
+                let value = self.context().wrap(value.ok_or(ErrorKind::InvalidNumber)?);
+                let e = Expr::Number(value);
+                self.push(e);
             }
-            Token::Number(n) => {
-                let e = Expr::Number(n);
-                self.memory.push(e);
+            Token::Constant(c) => {
+                let e = Expr::Constant(c);
+                self.push(e);
             }
             Token::Clear => {
-                self.memory.clear();
+                self.clear();
+            }
+            Token::Swap
+            | Token::Dup
+            | Token::Over
+            | Token::Rot
+            | Token::Roll
+            | Token::Pick
+            | Token::DropN
+            | Token::DupN
+            | Token::Depth
+            | Token::Reverse => {
+                self.shuffle(&token)?;
+            }
+            Token::Factor => {
+                let x = self.pop().ok_or(ErrorKind::MissingOperand)?;
+                let value = x.try_eval_in(&self.context());
+                let factors = value.ok().and_then(|x| x.factor());
+                for factor in factors.ok_or(ErrorKind::CannotFactor)? {
+                    self.push(Expr::Number(factor));
+                }
+            }
+            Token::Angle(angle) => {
+                self.set_angle(angle);
+            }
+            Token::Radix(radix) => {
+                self.set_radix(radix);
+            }
+            Token::Coordinates(coordinates) => {
+                self.set_coordinates(coordinates);
+            }
+            Token::Record => {
+                self.recording = Some(Recording::default());
+            }
+            Token::End => {
+                let recording = self.recording.take().ok_or(ErrorKind::NotRecording)?;
+                let name = recording.name.ok_or(ErrorKind::ExpectedName)?;
+                // A stray keyword would otherwise only fail once the macro plays
+                let tokens = Token::split(&join(&recording.tokens, " "));
+                balanced(&tokens).map_err(|(kind, _)| kind)?;
+                self.set_macro(name, recording.tokens);
+            }
+            Token::Identifier(name) => {
+                self.identifier(name)?;
+            }
+            Token::Name(name) => {
+                self.push(Expr::Variable(name));
+            }
+            Token::Eval => {
+                let tokens = match self.pop().ok_or(ErrorKind::MissingOperand)? {
+                    Expr::Program(tokens) => tokens,
+                    Expr::Variable(name) => {
+                        self.program(&name).ok_or(ErrorKind::ExpectedProgram)?
+                    }
+                    _ => return Err(ErrorKind::ExpectedProgram),
+                };
+                self.play(&tokens)?;
+            }
+            Token::If
+            | Token::Then
+            | Token::Else
+            | Token::For
+            | Token::Next
+            | Token::While
+            | Token::Repeat
+            | Token::ProgramOpen
+            | Token::ProgramClose => return Err(ErrorKind::UnexpectedToken),
+            Token::Store => {
+                let name = self.pop().ok_or(ErrorKind::ExpectedName)?;
+                let register = self.register(&name).transpose()?;
+                let value = self.pop();
+                match (name, register) {
+                    (_, Some(register)) => {
+                        let value = value.ok_or(ErrorKind::MissingOperand)?;
+                        self.set_register(register, Some(value));
+                    }
+                    (Expr::Variable(name), None) => {
+                        let value = value.ok_or(ErrorKind::MissingOperand)?;
+                        self.store(name, value);
+                    }
+                    _ => return Err(ErrorKind::ExpectedName),
+                }
+            }
+            Token::Recall => {
+                let name = self.pop().ok_or(ErrorKind::ExpectedName)?;
+                let value = match (&name, self.register(&name).transpose()?) {
+                    (_, Some(register)) => {
+                        let value = self.registers.get(&register);
+                        value.ok_or(ErrorKind::EmptyRegister)?
+                    }
+                    (Expr::Variable(name), None) => {
+                        let value = self.variables.get(name);
+                        value.ok_or(ErrorKind::UndefinedVariable)?
+                    }
+                    _ => return Err(ErrorKind::ExpectedName),
+                };
+                self.push(value.clone());
+            }
+            Token::MemoryAdd | Token::MemorySubtract => {
+                let x = Box::from(self.pop().ok_or(ErrorKind::MissingOperand)?);
+                let memory = self.registers.get(&0).cloned().map(Box::from);
+                let value = match (token, memory) {
+                    (Token::MemoryAdd, Some(memory)) => Expr::Add(memory, x),
+                    (_, Some(memory)) => Expr::Subtract(memory, x),
+                    (Token::MemoryAdd, None) => *x,
+                    (_, None) => Expr::Negate(x),
+                };
+                self.set_register(0, Some(value));
+            }
+            Token::MemoryClear => {
+                if self.registers.contains_key(&0) {
+                    self.set_register(0, None);
+                }
+            }
+            Token::MemoryRecall => {
+                let value = self.registers.get(&0).cloned();
+                self.push(value.ok_or(ErrorKind::EmptyRegister)?);
             }
             Token::Pop => {
-                self.memory.pop();
+                self.pop();
+            }
+            Token::Function(function, count) => {
+                let count = count.unwrap_or(*function.arity().start());
+                if !function.arity().contains(&count) {
+                    return Err(ErrorKind::Arity);
+                }
+                if self.memory.len() < count {
+                    return Err(ErrorKind::MissingOperands);
+                }
+                let mut args: Vec<Expr> = (0..count).filter_map(|_| self.pop()).collect();
+                args.reverse();
+                self.push(Expr::Function(function, args));
             }
-            Token::Unknown(t) => return Err(format!("Unknown token: {}", t)),
             Token::GroupOpen | Token::GroupClose => {}
+            token => {
+                let operator = Operator::find(&token).ok_or(ErrorKind::UnknownToken)?;
+                let e = match operator.fixity {
+                    Fixity::Infix(expr) => {
+                        let y = self.pop().ok_or(ErrorKind::MissingOperands)?;
+                        let x = self.pop().ok_or(ErrorKind::MissingOperand)?;
+                        expr(Box::from(x), Box::from(y))
+                    }
+                    Fixity::Prefix(expr) | Fixity::Postfix(expr) | Fixity::Function(expr) => {
+                        let x = self.pop().ok_or(ErrorKind::MissingOperand)?;
+                        expr(Box::from(x))
+                    }
+                };
+                self.push(e);
+            }
         };
         Ok(())
     }
 }
 
+/// How many tokens the control structure the given ones start with spans, up to its closing
+/// token, or 1 if they start with any other token. An `if` takes a single `else`.
+fn structure(tokens: &[(Token, Span)]) -> Result<usize, (ErrorKind, Span)> {
+    // The structures opened so far, along with whether they had an `else`
+    let mut open = Vec::new();
+    for (position, (token, span)) in tokens.iter().enumerate() {
+        match token {
+            Token::If | Token::While | Token::For | Token::ProgramOpen => open.push((token, false)),
+            Token::Else => match open.last_mut() {
+                Some((Token::If, true)) => return Err((ErrorKind::UnexpectedToken, *span)),
+                Some((Token::If, otherwise)) => *otherwise = true,
+                _ => {}
+            },
+            Token::End | Token::Next | Token::ProgramClose => match (open.pop(), token) {
+                (None, _) => return Ok(1),
+                (Some((Token::If | Token::While, _)), Token::End)
+                | (Some((Token::For, _)), Token::Next)
+                | (Some((Token::ProgramOpen, _)), Token::ProgramClose) => {}
+                _ => return Err((ErrorKind::UnexpectedToken, *span)),
+            },
+            _ => {}
+        }
+        if open.is_empty() {
+            return Ok(position + 1);
+        }
+    }
+    Err((ErrorKind::UnclosedStructure, tokens[0].1))
+}
+
+/// Checks that tokens hold whole control structures, with none of their keywords out of one.
+fn balanced(tokens: &[(Token, Span)]) -> Result<(), (ErrorKind, Span)> {
+    let mut rest = tokens;
+    while let Some(((token, span), _)) = rest.split_first() {
+        let length = structure(rest)?;
+        let keyword = matches!(
+            token,
+            Token::Then
+                | Token::Else
+                | Token::Next
+                | Token::Repeat
+                | Token::End
+                | Token::ProgramClose
+        );
+        if length == 1 && keyword {
+            return Err((ErrorKind::UnexpectedToken, *span));
+        }
+        rest = &rest[length..];
+    }
+    Ok(())
+}
+
+/// Splits the tokens of a control structure at the first given keyword which is not part of
+/// a nested one.
+fn split<'a>(tokens: &'a [(Token, Span)], keyword: &Token) -> Option<Tokens<'a>> {
+    let mut position = 0;
+    while position < tokens.len() {
+        if tokens[position].0 == *keyword {
+            return Some((&tokens[..position], &tokens[position + 1..]));
+        }
+        position += structure(&tokens[position..]).ok()?;
+    }
+    None
+}
+
+/// Tokens of a control structure before and after one of its keywords.
+type Tokens<'a> = (&'a [(Token, Span)], &'a [(Token, Span)]);
+
+/// Fails unless a stack has the given number of entries, with the error of a single missing
+/// operand if only one is.
+fn operands(memory: &[Expr], count: usize) -> Result<(), ErrorKind> {
+    match count.saturating_sub(memory.len()) {
+        0 => Ok(()),
+        1 => Err(ErrorKind::MissingOperand),
+        _ => Err(ErrorKind::MissingOperands),
+    }
+}
+
+/// Writes the stack as postfix input, with integer literals in the radix setting.
 impl Display for Calc {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&join(self.memory.clone(), " "))
+        let number = |x: &Value| self.to_radix(x).unwrap_or_else(|| x.to_string());
+        let memory = self.memory.iter().map(|e| e.to_string_with(&number));
+        f.write_str(&join(memory, " "))
     }
 }
 
@@ -133,6 +1237,23 @@ impl Default for Calc {
     fn default() -> Self {
         Calc {
             memory: Vec::with_capacity(100),
+            backend: Backend::default(),
+            precision: Precision::default(),
+            angle: Angle::default(),
+            coordinates: Coordinates::default(),
+            radix: Radix::default(),
+            word: Word::default(),
+            policy: Policy::default(),
+            variables: Variables::new(),
+            functions: Definitions::new(),
+            registers: Registers::new(),
+            journal: Journal::default(),
+            macros: Macros::new(),
+            recording: None,
+            steps: Steps::default(),
+            changes: Vec::new(),
+            nesting: 0,
+            locals: Vec::new(),
         }
     }
 }
@@ -140,6 +1261,9 @@ impl Default for Calc {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calc::context::Rounding;
+    use crate::calc::error::EvalErrorKind;
+    use crate::calc::token::Span;
     use rstest::*;
 
     #[rstest]
@@ -150,6 +1274,23 @@ mod tests {
     #[case("2 2 /", 1.0)]
     #[case("2 3 ^", 8.0)]
     #[case("4 sqrt", 2.0)]
+    #[case("3 neg", -3.0)]
+    #[case("-3 chs", 3.0)]
+    #[case("3 4 hypot", 5.0)]
+    #[case("1 5 3 max:3", 5.0)]
+    #[case("1 5 3 max", 5.0)]
+    #[case("100 log", 2.0)]
+    #[case("8 2 log:2", 3.0)]
+    #[case("0 cos", 1.0)]
+    #[case("1 exp ln", 1.0)]
+    #[case("1024 log2 -2.5 abs *", 25.0)]
+    #[case("-7 3 mod", 2.0)]
+    #[case("2.5 round 2.5 floor -", 1.0)]
+    #[case("pi 2 /", std::f64::consts::FRAC_PI_2)]
+    #[case("deg π sin", std::f64::consts::PI.to_radians().sin())]
+    #[case("1.5 2 *", 3.0)]
+    #[case(".25 1e-2 +", 0.26)]
+    #[case("1_000 2.5e2 /", 4.0)]
     fn should_parse_postfix(#[case] input: &str, #[case] output: f64) {
         let calc = Calc::postfix(input).unwrap();
         let expr = calc.eval().pop().unwrap();
@@ -164,6 +1305,27 @@ mod tests {
     #[case("2 / 2", 1.0)]
     #[case("sqrt 4", 2.0)]
     #[case("3 + 4 * 2 / ( 1 - 5 ) ^ 2 ^ 3", 3.0001220703125)]
+    #[case("1.5 * 2", 3.0)]
+    #[case(".25 + 1e-2", 0.26)]
+    #[case("1_000 / 2.5e2", 4.0)]
+    #[case("-3 + 5", 2.0)]
+    #[case("2 * -4", -8.0)]
+    #[case("-2 ^ 2", -4.0)]
+    #[case("2 ^ -2", 0.25)]
+    #[case("+2 - -2", 4.0)]
+    #[case("max(1, 5, 3)", 5.0)]
+    #[case("log(8, 2) + log 100", 5.0)]
+    #[case("cos 0 + sin(0)", 1.0)]
+    #[case("log10(1000) * log2 8", 9.0)]
+    #[case("mod(-7, 3) + abs(-2)", 4.0)]
+    #[case("sign(-3) * ceil 1.2", -2.0)]
+    #[case("cos(τ)", 1.0)]
+    #[case("ln e", 1.0)]
+    #[case("5! + 3²", 129.0)]
+    #[case("-2³", -8.0)]
+    #[case("200 + 10%", 220.0)]
+    #[case("200 - 10%", 180.0)]
+    #[case("200 * 10%", 20.0)]
     fn should_parse_infix(#[case] input: &str, #[case] output: f64) {
         let calc = Calc::infix(input).unwrap();
         let expr = calc.eval().pop().unwrap();
@@ -177,6 +1339,11 @@ mod tests {
     #[case("2 2 *", "2 2")]
     #[case("2 2 /", "2 2")]
     #[case("4 sqrt", "4")]
+    #[case("4 neg", "4")]
+    #[case("1 2 3 max:3", "1 2 3")]
+    #[case("1 cos", "1")]
+    #[case("7 3 mod", "7 3")]
+    #[case("pi 2 *", "pi 2")]
     fn should_undo(#[case] input: &str, #[case] output: &str) {
         let undo = format!("{} undo", input);
         let result = Calc::postfix(undo.as_str()).unwrap();
@@ -207,23 +1374,762 @@ mod tests {
     }
 
     #[rstest]
-    #[case("_", "Unknown token: _")]
-    #[case("+", "Missing operands")]
-    #[case("1 +", "Missing operand")]
-    #[case("-", "Missing operands")]
-    #[case("2 -", "Missing operand")]
-    #[case("*", "Missing operands")]
-    #[case("3 *", "Missing operand")]
-    #[case("/", "Missing operands")]
-    #[case("4 /", "Missing operand")]
-    #[case("^", "Missing operands")]
-    #[case("4 ^", "Missing operand")]
-    #[case("sqrt", "Missing operand")]
-    #[case("undo", "Nothing to undo")]
-    fn should_error(#[case] input: &str, #[case] error: &str) {
+    #[case("1 2 swap", "2 1")]
+    #[case("1 2 dup", "1 2 2")]
+    #[case("1 2 over", "1 2 1")]
+    #[case("1 2 3 rot", "2 3 1")]
+    #[case("1 2 3 4 3 roll", "1 3 4 2")]
+    #[case("1 2 3 0 roll", "1 2 3")]
+    #[case("1 2 3 3 pick", "1 2 3 1")]
+    #[case("1 2 3 1 1 + dropn", "1")]
+    #[case("1 2 3 2 dupn", "1 2 3 2 3")]
+    #[case("1 2 depth", "1 2 2")]
+    #[case("1 2 3 reverse", "3 2 1")]
+    fn should_shuffle(#[case] input: &str, #[case] output: &str) {
+        let result = Calc::postfix(input).unwrap();
+        assert_eq!(result.to_string(), output);
+        // Undone as a whole, count included
+        let undo = format!("{} undo", input);
+        let result = Calc::postfix(undo.as_str()).unwrap();
+        let (before, _) = input.rsplit_once(' ').unwrap();
+        let before = Calc::postfix(before).unwrap();
+        assert_eq!(result.to_string(), before.to_string());
+        let redo = format!("{} undo redo", input);
+        let result = Calc::postfix(redo.as_str()).unwrap();
+        assert_eq!(result.to_string(), output);
+    }
+
+    #[test]
+    fn should_undo_and_redo() {
+        let mut calc = Calc::postfix("1 2 3 + swap").unwrap();
+        calc.input(Format::Postfix("clear deg")).unwrap();
+        calc.input(Format::Infix("x = 4")).unwrap();
+        calc.input(Format::Postfix("undo undo")).unwrap();
+        assert!(calc.variables().is_empty());
+        assert_eq!(calc.angle(), Angle::Radians);
+        calc.input(Format::Postfix("undo")).unwrap();
+        assert_eq!(calc.to_string(), "2 3 + 1");
+        calc.input(Format::Infix("undo")).unwrap();
+        assert_eq!(calc.to_string(), "1 2 3 +");
+        calc.input(Format::Postfix("redo redo redo")).unwrap();
+        assert_eq!(calc.to_string(), "");
+        assert_eq!(calc.angle(), Angle::Degrees);
+        // Anything else leaves nothing to redo
+        calc.input(Format::Postfix("undo 5")).unwrap();
+        let error = calc.input(Format::Postfix("redo")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NothingToRedo);
+        assert_eq!(calc.to_string(), "5");
+        assert_eq!(calc.angle(), Angle::Radians);
+    }
+
+    #[test]
+    fn should_limit_undo() {
+        let mut calc = Calc::postfix("1 2 3 4").unwrap();
+        calc.set_undo_levels(2);
+        assert_eq!(calc.undo_levels(), 2);
+        let error = calc.input(Format::Postfix("undo undo undo")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NothingToUndo);
+        assert_eq!(calc.to_string(), "1 2 3 4");
+        calc.input(Format::Postfix("undo undo redo")).unwrap();
+        assert_eq!(calc.to_string(), "1 2 3");
+    }
+
+    #[test]
+    fn should_roll_back_journal() {
+        let mut calc = Calc::postfix("1 2 3").unwrap();
+        calc.set_undo_levels(2);
+        calc.input(Format::Postfix("undo")).unwrap();
+        let input = Format::Postfix("redo undo 4 5 6 undo drop drop drop swap");
+        assert!(calc.input(input).is_err());
+        assert_eq!(calc.to_string(), "1 2");
+        calc.input(Format::Postfix("redo undo undo")).unwrap();
+        assert_eq!(calc.to_string(), "1");
+        let error = calc.input(Format::Postfix("undo")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NothingToUndo);
+    }
+
+    #[test]
+    fn should_serialize_journal() {
+        let mut calc = Calc::postfix("1 2 + 3 swap").unwrap();
+        calc.set_undo_levels(10);
+        let json = serde_json::to_string(&calc).unwrap();
+        let mut calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(calc.undo_levels(), 10);
+        calc.input(Format::Postfix("undo undo")).unwrap();
+        assert_eq!(calc.to_string(), "1 2 +");
+        calc.input(Format::Postfix("undo redo")).unwrap();
+        assert_eq!(calc.to_string(), "1 2 +");
+        // Sessions saved before there was a journal have nothing to undo
+        let mut calc: Calc = serde_json::from_str(r#"{"memory": []}"#).unwrap();
+        assert_eq!(calc.undo_levels(), 100);
+        let error = calc.input(Format::Postfix("undo")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NothingToUndo);
+    }
+
+    #[rstest]
+    #[case("swap", ErrorKind::MissingOperands)]
+    #[case("1 swap", ErrorKind::MissingOperand)]
+    #[case("dup", ErrorKind::MissingOperand)]
+    #[case("1 rot", ErrorKind::MissingOperands)]
+    #[case("roll", ErrorKind::MissingOperand)]
+    #[case("1 2 3 roll", ErrorKind::MissingOperand)]
+    #[case("1 0 pick", ErrorKind::InvalidCount)]
+    #[case("1 -1 dropn", ErrorKind::InvalidCount)]
+    #[case("1 2 0.5 dupn", ErrorKind::InvalidCount)]
+    #[case("1 5 dupn", ErrorKind::MissingOperands)]
+    fn should_not_shuffle(#[case] input: &str, #[case] kind: ErrorKind) {
+        let error = Calc::postfix(input).unwrap_err();
+        assert_eq!(error.kind, kind);
+    }
+
+    #[rstest]
+    #[case("_", ErrorKind::UnknownToken, "Unknown token: _")]
+    #[case("+", ErrorKind::MissingOperands, "Missing operands")]
+    #[case("1 +", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("-", ErrorKind::MissingOperands, "Missing operands")]
+    #[case("2 -", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("*", ErrorKind::MissingOperands, "Missing operands")]
+    #[case("3 *", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("/", ErrorKind::MissingOperands, "Missing operands")]
+    #[case("4 /", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("^", ErrorKind::MissingOperands, "Missing operands")]
+    #[case("4 ^", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("sqrt", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("neg", ErrorKind::MissingOperand, "Missing operand")]
+    #[case("1 2 max:3", ErrorKind::MissingOperands, "Missing operands")]
+    #[case("1 hypot:1", ErrorKind::Arity, "Wrong number of arguments: hypot:1")]
+    #[case("undo", ErrorKind::NothingToUndo, "Nothing to undo")]
+    fn should_error(#[case] input: &str, #[case] kind: ErrorKind, #[case] error: &str) {
         let result = Calc::postfix(input);
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), error);
+        let result = result.unwrap_err();
+        assert_eq!(result.kind, kind);
+        assert_eq!(result.to_string(), error);
+    }
+
+    #[rstest]
+    #[case(Format::Postfix("2 3 + _"), "_", Span::new(6, 7))]
+    #[case(Format::Postfix("2  foo +"), "foo", Span::new(3, 6))]
+    #[case(Format::Postfix("1 2 + * 3"), "*", Span::new(6, 7))]
+    #[case(Format::Infix("2 + $"), "$", Span::new(4, 5))]
+    #[case(Format::Infix("(2 + 3"), "(", Span::new(0, 1))]
+    #[case(Format::Infix("2 + 3)"), ")", Span::new(5, 6))]
+    #[case(Format::Infix("1 + ()"), "()", Span::new(4, 6))]
+    #[case(Format::Infix("2 3"), "3", Span::new(2, 3))]
+    fn should_error_at(#[case] input: Format, #[case] token: &str, #[case] span: Span) {
+        let error = Calc::default().input(input).unwrap_err();
+        assert_eq!(error.token, token);
+        assert_eq!(error.span, span);
+    }
+
+    #[rstest]
+    #[case("2 3 + _")]
+    #[case("rm rm rm 4 *")]
+    #[case("clear 1 +")]
+    #[case("undo undo undo undo undo undo")]
+    #[case("+ + 2 sqrt + +")]
+    #[case("swap dup 9 dropn")]
+    fn should_rollback(#[case] input: &str) {
+        let mut calc = Calc::postfix("1 2 3 + 4").unwrap();
+        assert!(calc.input(Format::Postfix(input)).is_err());
+        assert_eq!(calc.to_string(), "1 2 3 + 4");
+        calc.input(Format::Postfix("+")).unwrap();
+        assert_eq!(calc.to_string(), "1 2 3 + 4 +");
+    }
+
+    #[rstest]
+    #[case("90 sin", Angle::Radians, 90f64.sin())]
+    #[case("deg 90 sin", Angle::Degrees, 1.0)]
+    #[case("grad 1 acos 100 +", Angle::Gradians, 100.0)]
+    #[case("deg rad 0 cos", Angle::Radians, 1.0)]
+    fn should_use_angle(#[case] input: &str, #[case] angle: Angle, #[case] output: f64) {
+        let calc = Calc::postfix(input).unwrap();
+        assert_eq!(calc.angle(), angle);
+        assert_eq!(calc.eval(), vec![output]);
+    }
+
+    #[test]
+    fn should_keep_angle() {
+        let mut calc = Calc::infix("sin(90)").unwrap();
+        calc.input(Format::Infix("deg")).unwrap();
+        assert_eq!(calc.eval(), vec![1.0]);
+        assert!(calc.input(Format::Postfix("rad _")).is_err());
+        assert_eq!(calc.angle(), Angle::Degrees);
+    }
+
+    #[rstest]
+    #[case("1 2 0 / +", EvalErrorKind::DivisionByZero, "2 0 /")]
+    #[case("2 -1 sqrt *", EvalErrorKind::Domain, "-1 sqrt")]
+    #[case("10 400 ^", EvalErrorKind::Overflow, "10 400 ^")]
+    #[case("inf 0 *", EvalErrorKind::NotANumber, "inf 0 *")]
+    #[case("4 2 - sqrt 1 0 /", EvalErrorKind::DivisionByZero, "1 0 /")]
+    #[case("1 neg !", EvalErrorKind::Domain, "1 neg !")]
+    #[case("171 !", EvalErrorKind::Overflow, "171 !")]
+    fn should_try_eval(#[case] input: &str, #[case] kind: EvalErrorKind, #[case] expr: &str) {
+        let mut calc = Calc::postfix(input).unwrap();
+        let error = calc.try_eval().unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.expr.to_string(), expr);
+        // Lenient evaluation gives what eval does
+        calc.set_policy(Policy::Lenient);
+        let values = calc.try_eval().unwrap();
+        assert_eq!(format!("{:?}", values), format!("{:?}", calc.eval()));
+    }
+
+    #[test]
+    fn should_assign_infix() {
+        let mut calc = Calc::infix("x = 3 * 4").unwrap();
+        assert!(calc.is_empty());
+        calc.input(Format::Infix("x ^ 2")).unwrap();
+        assert_eq!(calc.to_string(), "x 2 ^");
+        assert_eq!(calc.eval(), vec![144.0]);
+        // Assignments take the current value of the variables they refer to
+        calc.input(Format::Infix("x = x + 1")).unwrap();
+        assert_eq!(calc.variables()["x"].to_string(), "3 4 * 1 +");
+        assert_eq!(calc.eval(), vec![169.0]);
+    }
+
+    #[test]
+    fn should_store_postfix() {
+        let mut calc = Calc::postfix("12 'x sto").unwrap();
+        assert!(calc.is_empty());
+        calc.input(Format::Postfix("x rcl x 2 ^")).unwrap();
+        assert_eq!(calc.to_string(), "12 x 2 ^");
+        assert_eq!(calc.eval(), vec![12.0, 144.0]);
+    }
+
+    #[test]
+    fn should_use_registers() {
+        let mut calc = Calc::postfix("1 2 + 1 sto 12 'x sto 5 m+").unwrap();
+        assert!(calc.is_empty());
+        calc.input(Format::Postfix("x 0 rcl 1 rcl")).unwrap();
+        assert_eq!(calc.to_string(), "x 5 1 2 +");
+        calc.input(Format::Postfix("clear 2 m- 10 m+ mr")).unwrap();
+        assert_eq!(calc.to_string(), "5 2 - 10 +");
+        assert_eq!(calc.value(&calc.registers()[&0]), 13.0);
+        calc.input(Format::Infix("mc")).unwrap();
+        assert_eq!(calc.registers().len(), 1);
+        calc.input(Format::Infix("m-")).unwrap();
+        assert_eq!(calc.to_string(), "");
+        assert_eq!(calc.value(&calc.registers()[&0]), -13.0);
+        // Registers are part of the journal and of the session
+        calc.input(Format::Postfix("undo undo")).unwrap();
+        assert_eq!(calc.value(&calc.registers()[&0]), 13.0);
+        let json = serde_json::to_string(&calc).unwrap();
+        assert!(json.contains(r#""registers":{"0":"#));
+        let calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(calc.registers()[&1].to_string(), "1 2 +");
+    }
+
+    #[test]
+    fn should_record_macros() {
+        let mut calc = Calc::postfix("rec sq dup * end 3 sq").unwrap();
+        assert_eq!(calc.to_string(), "3 3 *");
+        // Recording goes on across inputs, and macros play one another
+        calc.input(Format::Postfix("rec quad")).unwrap();
+        calc.input(Format::Postfix("sq sq")).unwrap();
+        assert!(calc.is_recording());
+        calc.input(Format::Infix("end")).unwrap();
+        assert!(!calc.is_recording());
+        calc.input(Format::Postfix("clear 2 quad")).unwrap();
+        assert_eq!(calc.eval(), vec![16.0]);
+        // Playing a macro is undone at once
+        calc.input(Format::Postfix("undo")).unwrap();
+        assert_eq!(calc.to_string(), "2");
+        let text = calc.export_macros();
+        assert_eq!(text, "rec quad sq sq end\nrec sq dup * end");
+        // Macros are part of the session, and exported ones can be recorded again
+        let json = serde_json::to_string(&calc).unwrap();
+        let mut calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(calc.macros()["quad"], vec!["sq", "sq"]);
+        assert_eq!(
+            calc.remove_macro("sq"),
+            Some(vec![String::from("dup"), String::from("*")])
+        );
+        assert_eq!(calc.remove_macro("sq"), None);
+        calc.input(Format::Postfix(&text)).unwrap();
+        assert_eq!(calc.macros().len(), 2);
+        assert_eq!(calc.to_string(), "2");
+    }
+
+    #[rstest]
+    #[case(Format::Postfix("end"), ErrorKind::NotRecording, Span::new(0, 3))]
+    #[case(Format::Postfix("rec 1"), ErrorKind::ExpectedName, Span::new(4, 5))]
+    #[case(
+        Format::Postfix("rec a rec"),
+        ErrorKind::AlreadyRecording,
+        Span::new(6, 9)
+    )]
+    #[case(
+        Format::Postfix("rec a x$ end"),
+        ErrorKind::UnknownToken,
+        Span::new(6, 8)
+    )]
+    #[case(
+        Format::Postfix("rec a a end a"),
+        ErrorKind::Nesting,
+        Span::new(12, 13)
+    )]
+    #[case(
+        Format::Postfix("rec a 1 next end"),
+        ErrorKind::UnexpectedToken,
+        Span::new(13, 16)
+    )]
+    #[case(
+        Format::Postfix("rec a repeat end"),
+        ErrorKind::UnexpectedToken,
+        Span::new(13, 16)
+    )]
+    #[case(
+        Format::Postfix("rec a 1 while 1 repeat next end end"),
+        ErrorKind::UnexpectedToken,
+        Span::new(32, 35)
+    )]
+    fn should_not_record(#[case] input: Format, #[case] kind: ErrorKind, #[case] span: Span) {
+        let error = Calc::default().input(input).unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.span, span);
+    }
+
+    #[test]
+    fn should_roll_back_recording() {
+        let mut calc = Calc::postfix("rec a 1").unwrap();
+        let error = calc.input(Format::Infix("1 + 2")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::RecordingInfix);
+        let error = calc.input(Format::Postfix("2 end end")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::NotRecording);
+        assert!(calc.is_recording());
+        assert!(calc.macros().is_empty());
+        calc.input(Format::Postfix("+ end 2 a")).unwrap();
+        assert_eq!(calc.to_string(), "2 1 +");
+    }
+
+    #[rstest]
+    #[case("1 2 < 2 2 != 3 3 >=", vec![1.0, 0.0, 1.0])]
+    #[case("true false and true false or", vec![0.0, 1.0])]
+    #[case("3 0 < if then 1 else 2 end", vec![2.0])]
+    #[case("0 if then 1 else 1 if then 2 else 3 end end", vec![2.0])]
+    #[case("-3 dup 0 < if then neg end", vec![3.0])]
+    #[case("0 1 5 for i i + next", vec![15.0])]
+    #[case("1 2 for i 1 3 for j i j * next next", vec![1.0, 2.0, 3.0, 2.0, 4.0, 6.0])]
+    #[case("5 1 for i i next", vec![])]
+    #[case("1 while dup 100 < repeat 2 * end", vec![128.0])]
+    #[case("3 << 1 + >> eval", vec![4.0])]
+    #[case("<< dup * >> 'sq sto 4 sq 5 'sq eval", vec![16.0, 25.0])]
+    #[case(
+        "<< dup 1 > if then dup 1 - fact * end >> 'fact sto 5 fact",
+        vec![120.0]
+    )]
+    #[case("rec absval dup 0 < if then neg end end -4 absval", vec![4.0])]
+    fn should_run_programs(#[case] input: &str, #[case] values: Vec<f64>) {
+        let calc = Calc::postfix(input).unwrap();
+        assert_eq!(calc.eval(), values);
+    }
+
+    #[test]
+    fn should_compare_before_bitwise_operations() {
+        let calc = Calc::infix("1 < 2 and 3 < 4").unwrap();
+        assert_eq!(calc.eval(), vec![1.0]);
+    }
+
+    #[test]
+    fn should_keep_programs() {
+        let calc = Calc::postfix("<< 1 + >> << >> 2 3 ==").unwrap();
+        assert_eq!(calc.to_string(), "<< 1 + >> << >> 2 3 ==");
+        let json = serde_json::to_string(&calc).unwrap();
+        let calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(calc.to_string(), "<< 1 + >> << >> 2 3 ==");
+        // A whole control structure is undone at once
+        let mut calc = Calc::postfix("1 3 for i i next 4").unwrap();
+        calc.input(Format::Postfix("undo undo")).unwrap();
+        assert_eq!(calc.to_string(), "1 3");
+        // Loop counters are gone once the loop ends
+        let error = calc.input(Format::Postfix("for j next j"));
+        assert_eq!(error.unwrap_err().kind, ErrorKind::UndefinedVariable);
+        assert_eq!(calc.to_string(), "1 3");
+    }
+
+    #[test]
+    fn should_limit_steps() {
+        let mut calc = Calc::default();
+        assert_eq!(calc.step_limit(), 100_000);
+        calc.set_step_limit(10);
+        let error = calc.input(Format::Postfix("0 1 100 for i i + next"));
+        assert_eq!(error.unwrap_err().kind, ErrorKind::StepLimit);
+        assert!(calc.is_empty());
+        calc.input(Format::Postfix("0 1 3 for i i + next")).unwrap();
+        assert_eq!(calc.eval(), vec![6.0]);
+    }
+
+    #[rstest]
+    #[case("1 if then 2", ErrorKind::UnclosedStructure, Span::new(2, 4))]
+    #[case("1 if 2 end", ErrorKind::IncompleteStructure, Span::new(2, 4))]
+    #[case("1 2 for 3 next", ErrorKind::IncompleteStructure, Span::new(4, 7))]
+    #[case("for i next", ErrorKind::MissingOperands, Span::new(0, 3))]
+    #[case("1 nan for i next", ErrorKind::InvalidBounds, Span::new(6, 9))]
+    #[case("nan if then end", ErrorKind::InvalidCondition, Span::new(4, 6))]
+    #[case("1 next", ErrorKind::UnexpectedToken, Span::new(2, 6))]
+    #[case(
+        "0 if 1 then 2 else 3 else 4 end",
+        ErrorKind::UnexpectedToken,
+        Span::new(21, 25)
+    )]
+    #[case("<< 1 end >>", ErrorKind::UnexpectedToken, Span::new(5, 8))]
+    #[case("1 if then undo end", ErrorKind::UnexpectedToken, Span::new(10, 14))]
+    #[case("1 eval", ErrorKind::ExpectedProgram, Span::new(2, 6))]
+    #[case("<< x >> eval", ErrorKind::UndefinedVariable, Span::new(8, 12))]
+    #[case("1 if then x end", ErrorKind::UndefinedVariable, Span::new(10, 11))]
+    #[case("while 1 repeat end", ErrorKind::StepLimit, Span::new(0, 5))]
+    #[case("<< 'f eval >> 'f sto f", ErrorKind::Nesting, Span::new(21, 22))]
+    fn should_not_run_programs(#[case] input: &str, #[case] kind: ErrorKind, #[case] span: Span) {
+        let error = Calc::postfix(input).unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.span, span);
+    }
+
+    #[rstest]
+    #[case(Format::Infix("y + 1"), ErrorKind::UndefinedVariable, Span::new(0, 1))]
+    #[case(
+        Format::Postfix("y 1 +"),
+        ErrorKind::UndefinedVariable,
+        Span::new(0, 1)
+    )]
+    #[case(
+        Format::Postfix("'y rcl"),
+        ErrorKind::UndefinedVariable,
+        Span::new(3, 6)
+    )]
+    #[case(Format::Postfix("1 pi sto"), ErrorKind::ExpectedName, Span::new(5, 8))]
+    #[case(
+        Format::Postfix("1 -1 sto"),
+        ErrorKind::InvalidRegister,
+        Span::new(5, 8)
+    )]
+    #[case(
+        Format::Postfix("0.5 rcl"),
+        ErrorKind::InvalidRegister,
+        Span::new(4, 7)
+    )]
+    #[case(Format::Postfix("2 rcl"), ErrorKind::EmptyRegister, Span::new(2, 5))]
+    #[case(Format::Postfix("mr"), ErrorKind::EmptyRegister, Span::new(0, 2))]
+    #[case(Format::Infix("m-"), ErrorKind::MissingOperand, Span::new(0, 2))]
+    #[case(Format::Postfix("rcl"), ErrorKind::ExpectedName, Span::new(0, 3))]
+    #[case(Format::Postfix("'y sto"), ErrorKind::MissingOperand, Span::new(3, 6))]
+    fn should_error_on_variables(
+        #[case] input: Format,
+        #[case] kind: ErrorKind,
+        #[case] span: Span,
+    ) {
+        let error = Calc::default().input(input).unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.span, span);
+    }
+
+    #[rstest]
+    #[case("'x 'x sto x")]
+    #[case("'y 'x sto 'x 'y sto y")]
+    fn should_not_recurse_through_variables(#[case] input: &str) {
+        let calc = Calc::postfix(input).unwrap();
+        let error = calc.try_eval().unwrap_err();
+        assert_eq!(error.kind, EvalErrorKind::Recursion);
+        assert!(calc.eval()[0].is_nan());
+    }
+
+    #[test]
+    fn should_define_functions() {
+        let mut calc = Calc::infix("def f(x, y) = x^2 + y").unwrap();
+        assert!(calc.is_empty());
+        assert_eq!(calc.functions()["f"].params, vec!["x", "y"]);
+        calc.input(Format::Infix("f(3, 4)")).unwrap();
+        calc.input(Format::Postfix("1 2 f")).unwrap();
+        assert_eq!(calc.to_string(), "3 4 f 1 2 f");
+        assert_eq!(calc.eval(), vec![13.0, 3.0]);
+        // Parameters hide variables, which bodies otherwise see as they are when called
+        calc.input(Format::Infix("x = 10")).unwrap();
+        calc.input(Format::Infix("def g(y) = f(x, y) * 2")).unwrap();
+        calc.input(Format::Infix("g 1")).unwrap();
+        assert_eq!(calc.eval(), vec![13.0, 3.0, 202.0]);
+        // Redefining a function changes the values calling it
+        calc.input(Format::Infix("def f(x, y) = x - y")).unwrap();
+        assert_eq!(calc.eval(), vec![-1.0, -1.0, 18.0]);
+    }
+
+    #[test]
+    fn should_resolve_variables_lexically() {
+        let mut calc = Calc::postfix("'y 1 + 'x sto 2 'y sto").unwrap();
+        calc.input(Format::Infix("def f(y) = x")).unwrap();
+        calc.input(Format::Infix("f(5)")).unwrap();
+        // The y of x is the variable, not the parameter of the function reading x
+        assert_eq!(calc.eval(), vec![3.0]);
+    }
+
+    #[test]
+    fn should_stop_recursion() {
+        let mut calc = Calc::infix("def f(x) = f(x + 1)").unwrap();
+        calc.input(Format::Infix("f(1)")).unwrap();
+        assert!(calc.eval()[0].is_nan());
+    }
+
+    #[test]
+    fn should_serialize_functions() {
+        let calc = Calc::infix("def f(x) = 2 * x").unwrap();
+        let json = serde_json::to_string(&calc).unwrap();
+        let mut calc: Calc = serde_json::from_str(&json).unwrap();
+        calc.input(Format::Infix("f 21")).unwrap();
+        assert_eq!(calc.eval(), vec![42.0]);
+    }
+
+    #[rstest]
+    #[case("0.1 0.2 +", "0.3")]
+    #[case("1.10 2.25 +", "3.35")]
+    #[case("1 3 /", "0.3333333333333333333333333333")]
+    #[case("2 sqrt", "1.414213562373095048801688724")]
+    #[case("12345678901234567890.1 1 +", "12345678901234567891.1")]
+    #[case("1_000.005 round", "1000")]
+    #[case("19.99 3 * 0.07 *", "4.1979")]
+    #[case("200 10 % +", "220")]
+    #[case("2 0.5 ^", "1.414213562373095048801688724")]
+    #[case("1e1000 2 *", "2e+1000")]
+    #[case("1e-1000 2 *", "2e-1000")]
+    fn should_use_decimals(#[case] input: &str, #[case] output: &str) {
+        let mut calc = Calc::new(Backend::Decimal);
+        calc.input(Format::Postfix(input)).unwrap();
+        assert_eq!(calc.eval()[0].to_string(), output);
+    }
+
+    #[test]
+    fn should_round_decimals() {
+        let mut calc = Calc::new(Backend::Decimal);
+        calc.set_precision(Precision {
+            digits: 4,
+            rounding: Rounding::Down,
+        });
+        calc.input(Format::Infix("2 / 3 + sqrt 2")).unwrap();
+        assert_eq!(calc.eval()[0].to_string(), "2.0806");
+        // Powers are rounded too
+        calc.input(Format::Infix("2 ^ 0.5")).unwrap();
+        calc.input(Format::Infix("2 ^ 100")).unwrap();
+        assert_eq!(calc.eval()[1].to_string(), "1.414");
+        assert_eq!(calc.eval()[2].to_string(), "1267e+27");
+        calc.input(Format::Infix("1 / 0")).unwrap();
+        let error = calc.try_eval().unwrap_err();
+        assert_eq!(error.kind, EvalErrorKind::DivisionByZero);
+    }
+
+    #[rstest]
+    #[case(Format::Postfix("1 3 /"), "1/3")]
+    #[case(Format::Infix("1/3 + 1/6"), "1/2")]
+    #[case(Format::Postfix("0.1 0.2 +"), "3/10")]
+    #[case(Format::Postfix("4 9 / sqrt"), "2/3")]
+    #[case(Format::Postfix("2 3 / -2 ^"), "9/4")]
+    #[case(Format::Infix("(2/3)³ + 30!"), "7161827214929158583180328960000008/27")]
+    #[case(Format::Infix("3/4 + 10%"), "33/40")]
+    #[case(Format::Infix("2^100 + 1/2"), "2535301200456458802993406410753/2")]
+    #[case(Format::Postfix("2 sqrt"), "1.4142135623730951")]
+    #[case(Format::Postfix("pi 2 *"), "6.283185307179586")]
+    fn should_use_rationals(#[case] input: Format, #[case] output: &str) {
+        let mut calc = Calc::new(Backend::Rational);
+        calc.input(input).unwrap();
+        assert_eq!(calc.eval()[0].to_string(), output);
+    }
+
+    #[test]
+    fn should_write_fractions() {
+        let calc = Calc::postfix("0.5 7 2 /").unwrap();
+        assert_eq!(calc.to_string(), "0.5 7 2 /");
+        let mut calc = Calc::new(Backend::Rational);
+        calc.input(Format::Postfix("0.5 7 2 /")).unwrap();
+        assert_eq!(calc.to_string(), "1/2 7 2 /");
+        assert_eq!(format!("{:#}", calc.eval()[1]), "3 1/2");
+        let json = serde_json::to_string(&calc).unwrap();
+        let calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(calc.eval()[1].to_string(), "7/2");
+    }
+
+    #[rstest]
+    #[case(Format::Postfix("-1 sqrt"), "1i")]
+    #[case(Format::Postfix("4 neg sqrt"), "2i")]
+    #[case(Format::Infix("(3+4i) * (3-4i)"), "25")]
+    #[case(Format::Infix("abs(3+4i)"), "5")]
+    #[case(Format::Infix("conj(3+4i) + re(2i) + im(2i)"), "5-4i")]
+    #[case(Format::Postfix("-1 ln"), "3.141592653589793i")]
+    #[case(Format::Postfix("1i 2 ^"), "-1")]
+    #[case(Format::Postfix("-1 0.5 ^"), "1i")]
+    #[case(Format::Infix("i * i"), "-1")]
+    #[case(Format::Infix("3 + 4 * i"), "3+4i")]
+    #[case(Format::Postfix("2 'i sto i"), "2")]
+    #[case(Format::Postfix("deg 1i arg"), "90")]
+    fn should_use_complex_numbers(#[case] input: Format, #[case] output: &str) {
+        let mut calc = Calc::new(Backend::Complex);
+        calc.input(input).unwrap();
+        assert_eq!(calc.eval()[0].to_string(), output);
+    }
+
+    #[test]
+    fn should_write_complex_numbers() {
+        let mut calc = Calc::new(Backend::Complex);
+        calc.input(Format::Postfix("3 4i + deg polar")).unwrap();
+        assert_eq!(calc.coordinates(), Coordinates::Polar);
+        assert_eq!(calc.format(&calc.eval()[0]), "5∠53.13010235415598");
+        calc.input(Format::Infix("rect")).unwrap();
+        assert_eq!(calc.format(&calc.eval()[0]), "3+4i");
+        assert!(calc.input(Format::Postfix("polar _")).is_err());
+        assert_eq!(calc.coordinates(), Coordinates::Rectangular);
+    }
+
+    #[test]
+    fn should_serialize_complex_numbers() {
+        // Imaginary literals are complex whatever the backend
+        let calc = Calc::infix("2i * 2i").unwrap();
+        let json = serde_json::to_string(&calc).unwrap();
+        assert!(json.contains(r#"{"Complex":[0.0,2.0]}"#));
+        let calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(calc.to_string(), "2i 2i *");
+        assert_eq!(calc.eval()[0].to_string(), "-4");
+    }
+
+    #[rstest]
+    #[case(
+        Format::Postfix("2 200 ^"),
+        "1606938044258990275541962092341162602522202993782792835301376"
+    )]
+    #[case(
+        Format::Infix("2^64 * 2^64 - 1"),
+        "340282366920938463463374607431768211455"
+    )]
+    #[case(Format::Postfix("6 3 /"), "2")]
+    #[case(Format::Postfix("7 2 /"), "3.5")]
+    #[case(Format::Infix("-7 // 2"), "-3")]
+    #[case(Format::Infix("-7 rem 2"), "-1")]
+    #[case(Format::Infix("gcd(12, 18) + lcm(4, 6)"), "18")]
+    #[case(Format::Postfix("4 13 497 modpow"), "445")]
+    #[case(Format::Postfix("3 7 modinv"), "5")]
+    #[case(Format::Postfix("2 61 ^ 1 - isprime"), "1")]
+    #[case(Format::Postfix("1000 nextprime"), "1009")]
+    #[case(Format::Infix("30!"), "265252859812191058636308480000000")]
+    fn should_use_integers(#[case] input: Format, #[case] output: &str) {
+        let mut calc = Calc::new(Backend::Integer);
+        calc.input(input).unwrap();
+        assert_eq!(calc.eval()[0].to_string(), output);
+    }
+
+    #[test]
+    fn should_factor() {
+        let mut calc = Calc::new(Backend::Integer);
+        calc.input(Format::Postfix("7 360 factor")).unwrap();
+        assert_eq!(calc.to_string(), "7 2 2 2 3 3 5");
+        assert!(calc.input(Format::Postfix("factor _")).is_err());
+        assert_eq!(calc.to_string(), "7 2 2 2 3 3 5");
+        let error = calc.input(Format::Postfix("1 2 / factor")).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::CannotFactor);
+        // Floats are factored too, as long as they are integers
+        let mut calc = Calc::postfix("12 factor").unwrap();
+        assert_eq!(calc.eval(), vec![2.0, 2.0, 3.0]);
+        calc.input(Format::Infix("24 // 5")).unwrap();
+        assert_eq!(calc.eval()[3], 4.0);
+    }
+
+    #[test]
+    fn should_serialize_integers() {
+        let mut calc = Calc::new(Backend::Integer);
+        calc.input(Format::Postfix("2 100 ^")).unwrap();
+        let json = serde_json::to_string(&calc).unwrap();
+        assert!(json.contains(r#"{"Integer":"100"}"#));
+        let calc: Calc = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            calc.eval()[0].to_string(),
+            "1267650600228229401496703205376"
+        );
+        let error = Calc::new(Backend::Integer).input(Format::Postfix("1.5"));
+        assert_eq!(error.unwrap_err().kind, ErrorKind::InvalidNumber);
+    }
+
+    #[rstest]
+    #[case(Format::Postfix("0xFF 1 +"), 8, false, "0")]
+    #[case(Format::Postfix("0xFF"), 8, true, "-1")]
+    #[case(Format::Postfix("127 1 +"), 8, true, "-128")]
+    #[case(Format::Postfix("0 1 -"), 16, false, "65535")]
+    #[case(Format::Postfix("7 2 /"), 64, true, "3")]
+    #[case(Format::Postfix("-7 2 /"), 64, true, "-3")]
+    #[case(Format::Postfix("10 sqrt"), 64, true, "3")]
+    #[case(Format::Postfix("3 100 ^"), 8, false, "209")]
+    #[case(Format::Infix("0b1100 and 0b1010 or 0o1"), 8, false, "9")]
+    #[case(Format::Infix("not 0"), 8, false, "255")]
+    #[case(Format::Infix("1 shl 8"), 8, false, "0")]
+    #[case(Format::Infix("0x81 rol 1"), 8, false, "3")]
+    #[case(Format::Infix("0x81 ror 1"), 8, true, "-64")]
+    #[case(Format::Infix("-0x80 shr 4"), 8, true, "-8")]
+    #[case(
+        Format::Infix("abs 9223372036854775807"),
+        64,
+        true,
+        "9223372036854775807"
+    )]
+    #[case(Format::Infix("max(pi, 2)"), 64, true, "3")]
+    #[case(Format::Infix("sin 2"), 64, true, "0")]
+    #[case(Format::Infix("pi"), 64, true, "3")]
+    #[case(Format::Infix("-pi"), 64, true, "-3")]
+    #[case(Format::Infix("hypot(10, 3)"), 8, false, "10")]
+    #[case(Format::Infix("0x1FF"), 8, true, "-1")]
+    fn should_use_words(
+        #[case] input: Format,
+        #[case] bits: u32,
+        #[case] signed: bool,
+        #[case] output: &str,
+    ) {
+        let mut calc = Calc::new(Backend::Programmer);
+        calc.set_word(Word::new(bits, signed).unwrap());
+        calc.input(input).unwrap();
+        assert_eq!(calc.eval()[0].to_string(), output);
+    }
+
+    #[test]
+    fn should_write_radix() {
+        let mut calc = Calc::new(Backend::Programmer);
+        calc.set_word(Word::new(8, true).unwrap());
+        calc.input(Format::Postfix("255 0b11 + hex")).unwrap();
+        assert_eq!(calc.radix(), Radix::Hex);
+        assert_eq!(calc.to_string(), "0xFF 0x3 +");
+        assert_eq!(calc.format(&calc.eval()[0]), "0x2");
+        calc.input(Format::Postfix("-1 bin")).unwrap();
+        assert_eq!(calc.to_string(), "0b11111111 0b11 + 0b11111111");
+        assert_eq!(calc.format(&calc.eval()[1]), "0b11111111");
+        calc.input(Format::Infix("dec")).unwrap();
+        // Literals are read as words, so that 255 is -1 with signed 8 bit words
+        assert_eq!(calc.to_string(), "-1 3 + -1");
+        // Only integers are written in another base
+        let mut calc = Calc::postfix("0x10 2.5 oct").unwrap();
+        assert_eq!(calc.to_string(), "0o20 2.5");
+        assert!(calc.input(Format::Postfix("hex _")).is_err());
+        assert_eq!(calc.radix(), Radix::Oct);
+    }
+
+    #[test]
+    fn should_load_sessions() {
+        // As serialized before the calculator had numeric backends
+        let json = r#"{"memory":[{"type":"Number","values":1.5},{"type":"Number","values":2}]}"#;
+        let calc: Calc = serde_json::from_str(json).unwrap();
+        assert_eq!(calc.backend(), Backend::Float);
+        assert_eq!(calc.eval(), vec![1.5, 2.0]);
+        let calc: Calc = serde_json::from_str(&serde_json::to_string(&calc).unwrap()).unwrap();
+        assert_eq!(calc.to_string(), "1.5 2");
+    }
+
+    #[test]
+    fn should_rollback_functions() {
+        let mut calc = Calc::infix("def f(x) = x").unwrap();
+        assert!(calc.input(Format::Postfix("1 f _")).is_err());
+        assert!(calc.input(Format::Infix("def f(x) = x +")).is_err());
+        assert_eq!(calc.functions()["f"].body.to_string(), "x");
+        assert!(calc.is_empty());
+    }
+
+    #[test]
+    fn should_rollback_variables() {
+        let mut calc = Calc::postfix("1 'x sto").unwrap();
+        assert!(calc.input(Format::Postfix("2 'x sto 3 'y sto _")).is_err());
+        assert_eq!(calc.variables().len(), 1);
+        assert_eq!(calc.variables()["x"].eval(), 1.0);
     }
 
     #[rstest]
@@ -0,0 +1,95 @@
+use std::f64::consts;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Mathematical constants, kept by name in expressions and only turned into numbers when
+/// evaluated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Constant {
+    Pi,
+    E,
+    Tau,
+    Phi,
+    Inf,
+    Nan,
+    /// Booleans, which comparisons give and conditions test, standing for 1 and 0.
+    True,
+    False,
+}
+
+impl Constant {
+    pub fn new(name: &str) -> Option<Constant> {
+        match name {
+            "pi" | "π" => Some(Constant::Pi),
+            "e" => Some(Constant::E),
+            "tau" | "τ" => Some(Constant::Tau),
+            "phi" | "φ" => Some(Constant::Phi),
+            "inf" => Some(Constant::Inf),
+            "nan" => Some(Constant::Nan),
+            "true" => Some(Constant::True),
+            "false" => Some(Constant::False),
+            _ => None,
+        }
+    }
+
+    pub fn value(&self) -> f64 {
+        match self {
+            Constant::Pi => consts::PI,
+            Constant::E => consts::E,
+            Constant::Tau => consts::TAU,
+            // The golden ratio, (1 + sqrt 5) / 2
+            Constant::Phi => 1.618033988749895,
+            Constant::Inf => f64::INFINITY,
+            Constant::Nan => f64::NAN,
+            Constant::True => 1.0,
+            Constant::False => 0.0,
+        }
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constant::Pi => write!(f, "pi"),
+            Constant::E => write!(f, "e"),
+            Constant::Tau => write!(f, "tau"),
+            Constant::Phi => write!(f, "phi"),
+            Constant::Inf => write!(f, "inf"),
+            Constant::Nan => write!(f, "nan"),
+            Constant::True => write!(f, "true"),
+            Constant::False => write!(f, "false"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case("pi", Constant::Pi)]
+    #[case("π", Constant::Pi)]
+    #[case("e", Constant::E)]
+    #[case("tau", Constant::Tau)]
+    #[case("τ", Constant::Tau)]
+    #[case("phi", Constant::Phi)]
+    #[case("φ", Constant::Phi)]
+    #[case("inf", Constant::Inf)]
+    #[case("nan", Constant::Nan)]
+    #[case("true", Constant::True)]
+    #[case("false", Constant::False)]
+    fn should_parse(#[case] name: &str, #[case] constant: Constant) {
+        assert_eq!(Constant::new(name), Some(constant));
+    }
+
+    #[test]
+    fn should_evaluate() {
+        assert_eq!(Constant::Phi.value(), (1.0 + 5f64.sqrt()) / 2.0);
+        assert_eq!(Constant::Tau.value(), 2.0 * Constant::Pi.value());
+        assert!(Constant::Nan.value().is_nan());
+    }
+}
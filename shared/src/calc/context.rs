@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+use std::fmt::{Display, Formatter};
+
+use num::{BigInt, FromPrimitive, Integer, One, Signed};
+use serde::{Deserialize, Serialize};
+
+use crate::calc::error::EvalError;
+use crate::calc::expr::Expr;
+use crate::calc::number::{Backend, Value};
+
+/// Values stored under a name, which expressions refer to through [`Expr::Variable`].
+pub type Variables = BTreeMap<String, Expr>;
+
+/// Values stored in numbered registers with `sto`, the memory keys working on register 0.
+/// The variables are the named registers.
+pub type Registers = BTreeMap<usize, Expr>;
+
+/// Macros recorded with `rec name ... end`, as the text of their postfix tokens.
+pub type Macros = BTreeMap<String, Vec<String>>;
+
+/// The scope outside of any function call, which has no parameters.
+static GLOBAL: Variables = Variables::new();
+
+/// Functions defined in a session, which expressions call through [`Expr::Call`].
+pub type Definitions = BTreeMap<String, Definition>;
+
+/// How deep user-defined functions and variables may refer to each other before giving up with
+/// [`crate::calc::error::EvalErrorKind::Recursion`], which stops recursive definitions.
+pub const MAX_DEPTH: usize = 64;
+
+/// A function defined as `def f(x, y) = x^2 + y`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Definition {
+    pub params: Vec<String>,
+    pub body: Expr,
+}
+
+impl Definition {
+    /// Evaluates the body with the parameters bound to the arguments, in a scope of their own.
+    pub fn call(&self, args: &[Value], context: &Context) -> Result<Value, EvalError> {
+        let scope: Variables = self
+            .params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned().map(Expr::Number))
+            .collect();
+        let context = Context {
+            scope: &scope,
+            depth: context.depth + 1,
+            ..context.clone()
+        };
+        self.body.try_eval_in(&context)
+    }
+}
+
+/// Unit in which trigonometric functions take and return angles.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Angle {
+    Degrees,
+    #[default]
+    Radians,
+    Gradians,
+}
+
+impl Angle {
+    pub fn to_radians(&self, x: f64) -> f64 {
+        match self {
+            Angle::Degrees => x.to_radians(),
+            Angle::Radians => x,
+            Angle::Gradians => x * PI / 200.0,
+        }
+    }
+
+    pub fn from_radians(&self, x: f64) -> f64 {
+        match self {
+            Angle::Degrees => x.to_degrees(),
+            Angle::Radians => x,
+            Angle::Gradians => x * 200.0 / PI,
+        }
+    }
+}
+
+impl Display for Angle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Angle::Degrees => write!(f, "deg"),
+            Angle::Radians => write!(f, "rad"),
+            Angle::Gradians => write!(f, "grad"),
+        }
+    }
+}
+
+/// How complex numbers are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Coordinates {
+    /// As real and imaginary parts, like `3+4i`.
+    #[default]
+    Rectangular,
+    /// As modulus and argument, like `5∠0.9272952180016122`.
+    Polar,
+}
+
+impl Display for Coordinates {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Coordinates::Rectangular => write!(f, "rect"),
+            Coordinates::Polar => write!(f, "polar"),
+        }
+    }
+}
+
+/// What checked evaluation does with results IEEE 754 arithmetic has no number for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Policy {
+    /// Division by zero, domain errors, overflows and NaN are reported as errors.
+    #[default]
+    Strict,
+    /// They evaluate to infinities and NaN as they would with `f64`.
+    Lenient,
+}
+
+/// How results which cannot be exact are rounded to the precision.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Rounding {
+    /// To the nearest, ties to the even neighbor, which is unbiased.
+    #[default]
+    HalfEven,
+    /// To the nearest, ties away from zero.
+    HalfUp,
+    /// Towards zero.
+    Down,
+}
+
+/// Significant digits kept by decimal divisions, square roots and powers, and how they are
+/// rounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Precision {
+    pub digits: u64,
+    pub rounding: Rounding,
+}
+
+impl Default for Precision {
+    fn default() -> Self {
+        Precision {
+            digits: 28,
+            rounding: Rounding::default(),
+        }
+    }
+}
+
+/// Base in which integers are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Radix {
+    Bin,
+    Oct,
+    #[default]
+    Dec,
+    Hex,
+}
+
+impl Radix {
+    pub fn base(&self) -> u32 {
+        match self {
+            Radix::Bin => 2,
+            Radix::Oct => 8,
+            Radix::Dec => 10,
+            Radix::Hex => 16,
+        }
+    }
+
+    /// Writes an integer with the prefix of its base, like `0xFF`, as literals are read.
+    pub fn format(&self, x: &BigInt) -> String {
+        let sign = if x.is_negative() { "-" } else { "" };
+        let digits = x.abs().to_str_radix(self.base());
+        match self {
+            Radix::Bin => format!("{}0b{}", sign, digits),
+            Radix::Oct => format!("{}0o{}", sign, digits),
+            Radix::Dec => format!("{}{}", sign, digits),
+            Radix::Hex => format!("{}0x{}", sign, digits.to_uppercase()),
+        }
+    }
+}
+
+impl Display for Radix {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Radix::Bin => write!(f, "bin"),
+            Radix::Oct => write!(f, "oct"),
+            Radix::Dec => write!(f, "dec"),
+            Radix::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+/// Size of the machine words programmer mode computes with, which results wrap around as
+/// two's complement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "WordSize")]
+pub struct Word {
+    bits: u32,
+    signed: bool,
+}
+
+/// A word as saved in a session, which only becomes a [`Word`] if it has one of its sizes.
+#[derive(Deserialize)]
+struct WordSize {
+    bits: u32,
+    signed: bool,
+}
+
+impl TryFrom<WordSize> for Word {
+    type Error = String;
+
+    fn try_from(size: WordSize) -> Result<Word, String> {
+        Word::new(size.bits, size.signed).ok_or_else(|| format!("Invalid word size: {}", size.bits))
+    }
+}
+
+impl Word {
+    pub const SIZES: [u32; 4] = [8, 16, 32, 64];
+
+    /// A word of 8, 16, 32 or 64 bits.
+    pub fn new(bits: u32, signed: bool) -> Option<Word> {
+        Word::SIZES.contains(&bits).then_some(Word { bits, signed })
+    }
+
+    pub fn bits(&self) -> u32 {
+        self.bits
+    }
+
+    pub fn signed(&self) -> bool {
+        self.signed
+    }
+
+    /// The integer the low bits of a number stand for, as the word is signed or not.
+    pub fn wrap(&self, x: &BigInt) -> BigInt {
+        let x = self.unsigned(x);
+        let half = BigInt::one() << (self.bits - 1);
+        match self.signed && x >= half {
+            true => x - (half << 1),
+            false => x,
+        }
+    }
+
+    /// The low bits of a number as an unsigned integer, which is how negative numbers are
+    /// written in another base than 10.
+    pub fn unsigned(&self, x: &BigInt) -> BigInt {
+        x.mod_floor(&(BigInt::one() << self.bits))
+    }
+}
+
+impl Default for Word {
+    fn default() -> Self {
+        Word {
+            bits: 64,
+            signed: true,
+        }
+    }
+}
+
+impl Display for Word {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.signed { 'i' } else { 'u' };
+        write!(f, "{}{}", sign, self.bits)
+    }
+}
+
+/// Settings and variables an expression is evaluated with, taken from the [`crate::calc::Calc`] holding it.
+#[derive(Debug, Clone)]
+pub struct Context<'a> {
+    pub backend: Backend,
+    pub precision: Precision,
+    pub angle: Angle,
+    pub policy: Policy,
+    pub word: Word,
+    pub variables: &'a Variables,
+    pub definitions: &'a Definitions,
+    /// Parameters of the function being evaluated, which hide variables of the same name.
+    pub scope: &'a Variables,
+    /// How many function calls and variable lookups the evaluation is nested in.
+    pub depth: usize,
+}
+
+impl<'a> Context<'a> {
+    pub fn lookup(&self, name: &str) -> Option<&'a Expr> {
+        self.scope.get(name).or_else(|| self.variables.get(name))
+    }
+
+    /// The context the value of a variable is evaluated in, one level deeper and outside of
+    /// any call, so that its names refer to variables rather than to the parameters of the
+    /// function reading it.
+    pub fn global(&self) -> Context<'a> {
+        Context {
+            scope: &GLOBAL,
+            depth: self.depth + 1,
+            ..self.clone()
+        }
+    }
+
+    /// Wraps integers around the word size in programmer mode, leaving values alone otherwise.
+    /// Fractional results, like those of `sin` or `pi`, are truncated toward zero first.
+    pub fn wrap(&self, value: Value) -> Value {
+        match (self.backend, value) {
+            (Backend::Programmer, Value::Integer(x)) => Value::Integer(self.word.wrap(&x)),
+            (Backend::Programmer, Value::Float(x)) => match BigInt::from_f64(x.trunc()) {
+                Some(x) => Value::Integer(self.word.wrap(&x)),
+                None => Value::Float(x),
+            },
+            (_, value) => value,
+        }
+    }
+}
+
+impl Default for Context<'_> {
+    fn default() -> Self {
+        static VARIABLES: Variables = Variables::new();
+        static DEFINITIONS: Definitions = Definitions::new();
+        Context {
+            backend: Backend::default(),
+            precision: Precision::default(),
+            angle: Angle::default(),
+            policy: Policy::default(),
+            word: Word::default(),
+            variables: &VARIABLES,
+            definitions: &DEFINITIONS,
+            scope: &GLOBAL,
+            depth: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Angle::Degrees, 180.0)]
+    #[case(Angle::Radians, PI)]
+    #[case(Angle::Gradians, 200.0)]
+    fn should_convert(#[case] angle: Angle, #[case] half_turn: f64) {
+        assert_eq!(angle.to_radians(half_turn), PI);
+        assert_eq!(angle.from_radians(PI), half_turn);
+    }
+
+    #[rstest]
+    #[case(8, true, "255", "-1")]
+    #[case(8, true, "128", "-128")]
+    #[case(8, true, "-129", "127")]
+    #[case(8, false, "-1", "255")]
+    #[case(16, false, "65537", "1")]
+    #[case(64, true, "9223372036854775808", "-9223372036854775808")]
+    fn should_wrap(#[case] bits: u32, #[case] signed: bool, #[case] x: &str, #[case] y: &str) {
+        let word = Word::new(bits, signed).unwrap();
+        assert_eq!(word.wrap(&x.parse().unwrap()).to_string(), y);
+    }
+
+    #[rstest]
+    #[case(r#"{"bits":16,"signed":false}"#, Word::new(16, false))]
+    #[case(r#"{"bits":0,"signed":true}"#, None)]
+    #[case(r#"{"bits":12,"signed":true}"#, None)]
+    fn should_deserialize_words(#[case] json: &str, #[case] word: Option<Word>) {
+        assert_eq!(serde_json::from_str::<Word>(json).ok(), word);
+    }
+
+    #[rstest]
+    #[case(Radix::Hex, "-255", "-0xFF")]
+    #[case(Radix::Oct, "8", "0o10")]
+    #[case(Radix::Bin, "10", "0b1010")]
+    #[case(Radix::Dec, "-10", "-10")]
+    fn should_format(#[case] radix: Radix, #[case] x: &str, #[case] text: &str) {
+        assert_eq!(radix.format(&x.parse().unwrap()), text);
+    }
+
+    #[test]
+    fn should_call() {
+        let x = || Box::from(Expr::Variable(String::from("x")));
+        let definition = Definition {
+            params: vec![String::from("x"), String::from("y")],
+            body: Expr::Add(
+                Box::from(Expr::Power(x(), Box::from(Expr::Number(Value::Float(2.0))))),
+                Box::from(Expr::Variable(String::from("y"))),
+            ),
+        };
+        let variables = Variables::from([(String::from("x"), Expr::Number(Value::Float(10.0)))]);
+        let context = Context {
+            variables: &variables,
+            ..Context::default()
+        };
+        let args = [Value::Float(3.0), Value::Float(4.0)];
+        assert_eq!(definition.call(&args, &context).unwrap(), 13.0);
+        // Parameters only live as long as the call
+        assert_eq!(x().eval_in(&context), 10.0);
+    }
+}
@@ -0,0 +1,176 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+use crate::calc::expr::Expr;
+use crate::calc::token::Span;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ErrorKind {
+    UnknownToken,
+    MissingOperands,
+    MissingOperand,
+    NothingToUndo,
+    NothingToRedo,
+    UnexpectedOperand,
+    UnexpectedToken,
+    UnclosedGroup,
+    UnopenedGroup,
+    EmptyGroup,
+    Arity,
+    UndefinedVariable,
+    ExpectedName,
+    DuplicateParameter,
+    InvalidNumber,
+    CannotFactor,
+    InvalidCount,
+    InvalidRegister,
+    EmptyRegister,
+    NotRecording,
+    AlreadyRecording,
+    RecordingInfix,
+    Nesting,
+    UnclosedStructure,
+    IncompleteStructure,
+    InvalidCondition,
+    InvalidBounds,
+    ExpectedProgram,
+    StepLimit,
+    TooDeep,
+}
+
+/// An error raised while feeding input to a [`crate::calc::Calc`], pointing at the offending
+/// token and its byte span in the input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalcError {
+    pub kind: ErrorKind,
+    pub token: String,
+    pub span: Span,
+}
+
+impl CalcError {
+    pub fn new(kind: ErrorKind, input: &str, span: Span) -> CalcError {
+        CalcError {
+            kind,
+            token: String::from(&input[span.start..span.end]),
+            span,
+        }
+    }
+}
+
+impl Display for CalcError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ErrorKind::UnknownToken => write!(f, "Unknown token: {}", self.token),
+            ErrorKind::MissingOperands => write!(f, "Missing operands"),
+            ErrorKind::MissingOperand => write!(f, "Missing operand"),
+            ErrorKind::NothingToUndo => write!(f, "Nothing to undo"),
+            ErrorKind::NothingToRedo => write!(f, "Nothing to redo"),
+            ErrorKind::UnexpectedOperand => write!(f, "Unexpected operand: {}", self.token),
+            ErrorKind::UnexpectedToken => write!(f, "Unexpected token: {}", self.token),
+            ErrorKind::UnclosedGroup => write!(f, "Unclosed parenthesis"),
+            ErrorKind::UnopenedGroup => write!(f, "Unexpected closing parenthesis"),
+            ErrorKind::EmptyGroup => write!(f, "Empty parenthesis"),
+            ErrorKind::Arity => write!(f, "Wrong number of arguments: {}", self.token),
+            // The token may be the name itself, or the command recalling it
+            ErrorKind::UndefinedVariable => write!(f, "Undefined variable"),
+            ErrorKind::ExpectedName => write!(f, "Expected a name"),
+            ErrorKind::DuplicateParameter => write!(f, "Duplicate parameter: {}", self.token),
+            ErrorKind::InvalidNumber => write!(f, "Invalid number: {}", self.token),
+            // Either not an integer, or with prime factors too large to find
+            ErrorKind::CannotFactor => write!(f, "Cannot factor"),
+            // The count of a stack command, which must be a natural number
+            ErrorKind::InvalidCount => write!(f, "Invalid count"),
+            // Registers are numbered from 0
+            ErrorKind::InvalidRegister => write!(f, "Invalid register: {}", self.token),
+            ErrorKind::EmptyRegister => write!(f, "Empty register"),
+            ErrorKind::NotRecording => write!(f, "No macro is being recorded"),
+            ErrorKind::AlreadyRecording => write!(f, "A macro is already being recorded"),
+            // Macros hold postfix tokens, so only `end` can be typed in infix notation
+            ErrorKind::RecordingInfix => write!(f, "Macros are recorded in postfix notation"),
+            // Most likely a macro or a program running itself
+            ErrorKind::Nesting => write!(f, "Too many nested macros or programs: {}", self.token),
+            ErrorKind::UnclosedStructure => write!(f, "Unclosed structure: {}", self.token),
+            // Like an `if` without `then`, or a `for` without the name of its counter
+            ErrorKind::IncompleteStructure => write!(f, "Incomplete structure: {}", self.token),
+            ErrorKind::InvalidCondition => write!(f, "Invalid condition"),
+            ErrorKind::InvalidBounds => write!(f, "Invalid loop bounds"),
+            ErrorKind::ExpectedProgram => write!(f, "Expected a program"),
+            ErrorKind::StepLimit => write!(f, "Too many steps: {}", self.token),
+            // Like thousands of nested parenthesis, which would overflow the stack
+            ErrorKind::TooDeep => write!(f, "Expression nested too deeply: {}", self.token),
+        }
+    }
+}
+
+impl Error for CalcError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvalErrorKind {
+    DivisionByZero,
+    /// An argument out of the domain of a function, like the square root of a negative number.
+    Domain,
+    Overflow,
+    /// An operation with no meaningful result, like `inf - inf`.
+    NotANumber,
+    /// A variable or function which is not defined where the expression is evaluated.
+    Undefined,
+    Recursion,
+    /// A result the backend has no exact value for, like the square root of 2 as a fraction.
+    Inexact,
+    Program,
+}
+
+/// An error raised while evaluating an expression, holding the subtree that caused it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalError {
+    pub kind: EvalErrorKind,
+    pub expr: Expr,
+}
+
+impl EvalError {
+    pub fn new(kind: EvalErrorKind, expr: &Expr) -> EvalError {
+        EvalError {
+            kind,
+            expr: expr.clone(),
+        }
+    }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            EvalErrorKind::DivisionByZero => write!(f, "Division by zero: {}", self.expr),
+            EvalErrorKind::Domain => write!(f, "Out of domain: {}", self.expr),
+            EvalErrorKind::Overflow => write!(f, "Overflow: {}", self.expr),
+            EvalErrorKind::NotANumber => write!(f, "Not a number: {}", self.expr),
+            EvalErrorKind::Undefined => write!(f, "Undefined: {}", self.expr),
+            EvalErrorKind::Recursion => write!(f, "Too much recursion: {}", self.expr),
+            EvalErrorKind::Inexact => write!(f, "No exact value: {}", self.expr),
+            EvalErrorKind::Program => write!(f, "Programs have no value: {}", self.expr),
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc::number::Value;
+
+    #[test]
+    fn should_capture_token() {
+        let error = CalcError::new(ErrorKind::UnknownToken, "2 3 + _", Span::new(6, 7));
+        assert_eq!(error.token, "_");
+        assert_eq!(error.to_string(), "Unknown token: _");
+    }
+
+    #[test]
+    fn should_capture_expr() {
+        let expr = Expr::Sqrt(Box::from(Expr::Number(Value::Float(-1.0))));
+        let error = EvalError::new(EvalErrorKind::Domain, &expr);
+        assert_eq!(error.to_string(), "Out of domain: -1 sqrt");
+    }
+}
@@ -1,30 +1,231 @@
 use std::collections::VecDeque;
 use std::fmt::{Display, Formatter};
 
+use itertools::join;
 use serde::{Deserialize, Serialize};
 
+use crate::calc::constant::Constant;
+use crate::calc::context::{Context, Policy, Variables, MAX_DEPTH};
+use crate::calc::error::{EvalError, EvalErrorKind};
+use crate::calc::function::Function;
+use crate::calc::number::Value;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "values")]
 pub enum Expr {
-    Number(f64),
+    Number(Value),
+    Constant(Constant),
+    Variable(String),
     Add(Box<Expr>, Box<Expr>),
     Subtract(Box<Expr>, Box<Expr>),
     Divide(Box<Expr>, Box<Expr>),
     Multiply(Box<Expr>, Box<Expr>),
+    /// Integer division, as `x // y`.
+    Quotient(Box<Expr>, Box<Expr>),
+    /// Remainder of the integer division, as `x rem y`.
+    Remainder(Box<Expr>, Box<Expr>),
     Sqrt(Box<Expr>),
     Power(Box<Expr>, Box<Expr>),
+    Negate(Box<Expr>),
+    /// Factorial, as `x!`, extended to non-integers by the gamma function.
+    Factorial(Box<Expr>),
+    /// Hundredth of a number, as `x%`. Adding or subtracting it adds or subtracts that share
+    /// of the other operand, so that `200 + 10%` is 220.
+    Percent(Box<Expr>),
+    Function(Function, Vec<Expr>),
+    /// A call to a function defined in the session, by name.
+    Call(String, Vec<Expr>),
+    /// True or false, as 1 or 0 of the backend.
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    /// Postfix tokens quoted as `<< ... >>`, which have no value but are run with `eval`.
+    Program(String),
+}
+
+/// How [`Expr::Compare`] compares its operands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Comparison::Equal => write!(f, "=="),
+            Comparison::NotEqual => write!(f, "!="),
+            Comparison::Less => write!(f, "<"),
+            Comparison::LessOrEqual => write!(f, "<="),
+            Comparison::Greater => write!(f, ">"),
+            Comparison::GreaterOrEqual => write!(f, ">="),
+        }
+    }
 }
 
 impl Expr {
-    pub fn eval(&self) -> f64 {
+    pub fn eval(&self) -> Value {
+        self.eval_in(&Context::default())
+    }
+
+    /// Evaluates the expression with the settings of the calculator holding it, giving NaN
+    /// where the value cannot be computed.
+    pub fn eval_in(&self, context: &Context) -> Value {
+        let context = Context {
+            policy: Policy::Lenient,
+            ..context.clone()
+        };
+        self.try_eval_in(&context).unwrap_or(Value::Float(f64::NAN))
+    }
+
+    pub fn try_eval(&self) -> Result<Value, EvalError> {
+        self.try_eval_in(&Context::default())
+    }
+
+    /// Evaluates the expression with the backend of the context, failing on the first subtree
+    /// whose value the backend cannot compute or the policy of the context does not accept.
+    /// Programmer mode wraps every literal and intermediate result around the word size.
+    pub fn try_eval_in(&self, context: &Context) -> Result<Value, EvalError> {
+        match self {
+            Expr::Number(value) => Ok(context.wrap(value.clone())),
+            Expr::Constant(constant) => {
+                let value = context.backend.from_f64(constant.value());
+                let value = value.ok_or_else(|| EvalError::new(EvalErrorKind::Domain, self))?;
+                self.check(&[], context.wrap(value), context)
+            }
+            Expr::Variable(name) => {
+                let Some(value) = context.lookup(name) else {
+                    return Err(EvalError::new(EvalErrorKind::Undefined, self));
+                };
+                if context.depth >= MAX_DEPTH {
+                    return Err(EvalError::new(EvalErrorKind::Recursion, self));
+                }
+                value.try_eval_in(&context.global())
+            }
+            Expr::Call(name, args) => {
+                let Some(definition) = context.definitions.get(name) else {
+                    return Err(EvalError::new(EvalErrorKind::Undefined, self));
+                };
+                if context.depth >= MAX_DEPTH {
+                    return Err(EvalError::new(EvalErrorKind::Recursion, self));
+                }
+                let args: Vec<Value> = args
+                    .iter()
+                    .map(|x| x.try_eval_in(context))
+                    .collect::<Result<_, _>>()?;
+                definition.call(&args, context)
+            }
+            Expr::Program(_) => Err(EvalError::new(EvalErrorKind::Program, self)),
+            _ => {
+                let args: Vec<Value> = self
+                    .undo()
+                    .into_iter()
+                    .map(|x| x.try_eval_in(context))
+                    .collect::<Result<_, _>>()?;
+                let value = match (self, args.as_slice()) {
+                    (Expr::Add(_, y), [x, share]) if matches!(**y, Expr::Percent(_)) => {
+                        x.mul(share).and_then(|y| x.add(&y))
+                    }
+                    (Expr::Subtract(_, y), [x, share]) if matches!(**y, Expr::Percent(_)) => {
+                        x.mul(share).and_then(|y| x.sub(&y))
+                    }
+                    (Expr::Add(..), [x, y]) => x.add(y),
+                    (Expr::Subtract(..), [x, y]) => x.sub(y),
+                    (Expr::Divide(..), [x, y]) => x.div(y, context),
+                    (Expr::Multiply(..), [x, y]) => x.mul(y),
+                    (Expr::Quotient(..), [x, y]) => x.quotient(y),
+                    (Expr::Remainder(..), [x, y]) => x.remainder(y),
+                    (Expr::Sqrt(_), [x]) => x.sqrt(context),
+                    (Expr::Power(..), [x, y]) => x.pow(y, context),
+                    (Expr::Negate(_), [x]) => x.neg(),
+                    (Expr::Factorial(_), [x]) => x.factorial(),
+                    (Expr::Percent(_), [x]) => x.percent(context),
+                    (Expr::Function(function, _), args) => Value::apply(*function, args, context),
+                    (Expr::Compare(comparison, ..), [x, y]) => x.compare(*comparison, y),
+                    _ => unreachable!(),
+                };
+                let value = value.map_err(|kind| EvalError::new(kind, self))?;
+                self.check(&args, context.wrap(value), context)
+            }
+        }
+    }
+
+    /// Tells why the value of the expression, computed out of the values of its operands, is
+    /// not a number the policy accepts.
+    fn check(&self, args: &[Value], value: Value, context: &Context) -> Result<Value, EvalError> {
+        if context.policy == Policy::Lenient {
+            return Ok(value);
+        }
+        let finite = !args.is_empty() && args.iter().all(Value::is_finite);
+        let infinite = !value.is_nan() && !value.is_finite();
+        let kind = match (self, args) {
+            (
+                Expr::Divide(..)
+                | Expr::Quotient(..)
+                | Expr::Remainder(..)
+                | Expr::Function(Function::Mod, _),
+                [_, y],
+            ) if y.is_zero() => EvalErrorKind::DivisionByZero,
+            (Expr::Power(..), [x, y]) if x.is_zero() && y.is_negative() => {
+                EvalErrorKind::DivisionByZero
+            }
+            (
+                Expr::Sqrt(_)
+                | Expr::Power(..)
+                | Expr::Factorial(_)
+                | Expr::Quotient(..)
+                | Expr::Remainder(..)
+                | Expr::Function(..),
+                _,
+            ) if value.is_nan() => EvalErrorKind::Domain,
+            _ if value.is_nan() => EvalErrorKind::NotANumber,
+            // Only functions growing past the largest float overflow, the others reach infinity at
+            // their poles, like the logarithm of zero
+            (Expr::Function(function, _), _) if infinite && finite => match function {
+                Function::Exp | Function::Sinh | Function::Cosh | Function::Hypot => {
+                    EvalErrorKind::Overflow
+                }
+                _ => EvalErrorKind::Domain,
+            },
+            _ if infinite && finite => EvalErrorKind::Overflow,
+            _ => return Ok(value),
+        };
+        Err(EvalError::new(kind, self))
+    }
+
+    /// Replaces variables by the expressions they hold, so that the result no longer depends on
+    /// them.
+    pub fn resolve(&self, variables: &Variables) -> Expr {
+        let resolve = |x: &Expr| Box::from(x.resolve(variables));
         match self {
-            Expr::Number(value) => *value,
-            Expr::Add(x, y) => x.eval() + y.eval(),
-            Expr::Subtract(x, y) => x.eval() - y.eval(),
-            Expr::Divide(x, y) => x.eval() / y.eval(),
-            Expr::Multiply(x, y) => x.eval() * y.eval(),
-            Expr::Sqrt(x) => x.eval().sqrt(),
-            Expr::Power(x, y) => x.eval().powf(y.eval()),
+            Expr::Variable(name) => match variables.get(name) {
+                Some(value) => value.clone(),
+                None => self.clone(),
+            },
+            Expr::Number(_) | Expr::Constant(_) | Expr::Program(_) => self.clone(),
+            Expr::Add(x, y) => Expr::Add(resolve(x), resolve(y)),
+            Expr::Subtract(x, y) => Expr::Subtract(resolve(x), resolve(y)),
+            Expr::Divide(x, y) => Expr::Divide(resolve(x), resolve(y)),
+            Expr::Multiply(x, y) => Expr::Multiply(resolve(x), resolve(y)),
+            Expr::Quotient(x, y) => Expr::Quotient(resolve(x), resolve(y)),
+            Expr::Remainder(x, y) => Expr::Remainder(resolve(x), resolve(y)),
+            Expr::Sqrt(x) => Expr::Sqrt(resolve(x)),
+            Expr::Power(x, y) => Expr::Power(resolve(x), resolve(y)),
+            Expr::Negate(x) => Expr::Negate(resolve(x)),
+            Expr::Factorial(x) => Expr::Factorial(resolve(x)),
+            Expr::Percent(x) => Expr::Percent(resolve(x)),
+            Expr::Function(function, args) => Expr::Function(
+                *function,
+                args.iter().map(|x| x.resolve(variables)).collect(),
+            ),
+            Expr::Call(name, args) => Expr::Call(
+                name.clone(),
+                args.iter().map(|x| x.resolve(variables)).collect(),
+            ),
+            Expr::Compare(comparison, x, y) => Expr::Compare(*comparison, resolve(x), resolve(y)),
         }
     }
 
@@ -34,85 +235,304 @@ impl Expr {
             | Expr::Subtract(x, y)
             | Expr::Divide(x, y)
             | Expr::Multiply(x, y)
-            | Expr::Power(x, y) => {
+            | Expr::Quotient(x, y)
+            | Expr::Remainder(x, y)
+            | Expr::Power(x, y)
+            | Expr::Compare(_, x, y) => {
                 let mut q = VecDeque::with_capacity(2);
                 q.push_back(x.as_ref());
                 q.push_back(y.as_ref());
                 q
             }
-            Expr::Sqrt(x) => {
+            Expr::Sqrt(x) | Expr::Negate(x) | Expr::Factorial(x) | Expr::Percent(x) => {
                 let mut q = VecDeque::with_capacity(1);
                 q.push_back(x.as_ref());
                 q
             }
+            Expr::Function(_, args) | Expr::Call(_, args) => args.iter().collect(),
             _ => VecDeque::with_capacity(0),
         }
     }
 }
 
-impl Display for Expr {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+impl Expr {
+    /// Writes the expression in postfix notation, with numbers written by the given function.
+    pub fn to_string_with(&self, number: &dyn Fn(&Value) -> String) -> String {
+        let write = |x: &Expr| x.to_string_with(number);
+        let join = |args: &[Expr]| join(args.iter().map(write), " ");
         match self {
-            Expr::Add(x, y) => write!(f, "{} {} +", x, y),
-            Expr::Subtract(x, y) => write!(f, "{} {} -", x, y),
-            Expr::Divide(x, y) => write!(f, "{} {} /", x, y),
-            Expr::Multiply(x, y) => write!(f, "{} {} *", x, y),
-            Expr::Power(x, y) => write!(f, "{} {} ^", x, y),
-            Expr::Sqrt(x) => write!(f, "{} sqrt", x),
-            Expr::Number(x) => write!(f, "{}", x),
+            Expr::Add(x, y) => format!("{} {} +", write(x), write(y)),
+            Expr::Subtract(x, y) => format!("{} {} -", write(x), write(y)),
+            Expr::Divide(x, y) => format!("{} {} /", write(x), write(y)),
+            Expr::Multiply(x, y) => format!("{} {} *", write(x), write(y)),
+            Expr::Quotient(x, y) => format!("{} {} //", write(x), write(y)),
+            Expr::Remainder(x, y) => format!("{} {} rem", write(x), write(y)),
+            Expr::Power(x, y) => format!("{} {} ^", write(x), write(y)),
+            Expr::Sqrt(x) => format!("{} sqrt", write(x)),
+            Expr::Negate(x) => format!("{} neg", write(x)),
+            Expr::Factorial(x) => format!("{} !", write(x)),
+            Expr::Percent(x) => format!("{} %", write(x)),
+            // Only variadic calls need to tell how many arguments they take from the stack
+            Expr::Function(function, args) if args.len() == *function.arity().start() => {
+                format!("{} {}", join(args), function)
+            }
+            Expr::Function(function, args) => {
+                format!("{} {}:{}", join(args), function, args.len())
+            }
+            Expr::Call(name, args) if args.is_empty() => name.clone(),
+            Expr::Call(name, args) => format!("{} {}", join(args), name),
+            Expr::Compare(comparison, x, y) => format!("{} {} {}", write(x), write(y), comparison),
+            Expr::Program(tokens) if tokens.is_empty() => String::from("<< >>"),
+            Expr::Program(tokens) => format!("<< {} >>", tokens),
+            Expr::Number(x) => number(x),
+            Expr::Constant(x) => x.to_string(),
+            Expr::Variable(x) => x.clone(),
         }
     }
 }
 
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_string_with(&Value::to_string))
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use rstest::*;
+
     use super::Expr::*;
+    use crate::calc::constant::Constant as C;
+    use crate::calc::context::{Context, Definition, Definitions, Policy, Variables};
+    use crate::calc::error::EvalErrorKind as E;
+    use crate::calc::function::Function as F;
+    use crate::calc::number::Value::Float;
 
     #[test]
     fn add() {
-        let expr = Add(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Add(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         assert_eq!(expr.eval(), 4.0);
         assert_eq!(format!("{expr}"), "2 2 +");
     }
 
     #[test]
     fn subtract() {
-        let expr = Subtract(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Subtract(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         assert_eq!(expr.eval(), 0.0);
         assert_eq!(format!("{expr}"), "2 2 -");
     }
 
     #[test]
     fn multiply() {
-        let expr = Multiply(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Multiply(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         assert_eq!(expr.eval(), 4.0);
         assert_eq!(format!("{expr}"), "2 2 *");
     }
 
     #[test]
     fn divide() {
-        let expr = Divide(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Divide(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         assert_eq!(expr.eval(), 1.0);
         assert_eq!(format!("{expr}"), "2 2 /");
     }
 
+    #[test]
+    fn quotient() {
+        let expr = Quotient(
+            Box::from(Number(Float(-7.0))),
+            Box::from(Number(Float(2.0))),
+        );
+        assert_eq!(expr.eval(), -3.0);
+        assert_eq!(format!("{expr}"), "-7 2 //");
+        let expr = Remainder(
+            Box::from(Number(Float(-7.0))),
+            Box::from(Number(Float(2.0))),
+        );
+        assert_eq!(expr.eval(), -1.0);
+        assert_eq!(format!("{expr}"), "-7 2 rem");
+    }
+
     #[test]
     fn pow() {
-        let expr = Power(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Power(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         assert_eq!(expr.eval(), 4.0);
         assert_eq!(format!("{expr}"), "2 2 ^");
     }
 
     #[test]
     fn sqrt() {
-        let expr = Sqrt(Box::from(Number(4.0)));
+        let expr = Sqrt(Box::from(Number(Float(4.0))));
         assert_eq!(expr.eval(), 2.0);
         assert_eq!(format!("{expr}"), "4 sqrt");
     }
 
+    #[test]
+    fn negate() {
+        let expr = Negate(Box::from(Number(Float(4.0))));
+        assert_eq!(expr.eval(), -4.0);
+        assert_eq!(format!("{expr}"), "4 neg");
+    }
+
+    #[test]
+    fn factorial() {
+        let expr = Factorial(Box::from(Number(Float(5.0))));
+        assert_eq!(expr.eval(), 120.0);
+        assert_eq!(format!("{expr}"), "5 !");
+    }
+
+    #[test]
+    fn percent() {
+        let expr = Percent(Box::from(Number(Float(50.0))));
+        assert_eq!(expr.eval(), 0.5);
+        assert_eq!(format!("{expr}"), "50 %");
+        let expr = Subtract(Box::from(Number(Float(80.0))), Box::from(expr));
+        assert_eq!(expr.eval(), 40.0);
+        assert_eq!(format!("{expr}"), "80 50 % -");
+    }
+
+    #[test]
+    fn compare() {
+        let x = || Box::from(Number(Float(1.0)));
+        let expr = Compare(super::Comparison::Less, x(), Box::from(Constant(C::True)));
+        assert_eq!(expr.eval(), 0.0);
+        assert_eq!(format!("{expr}"), "1 true <");
+        let expr = Compare(super::Comparison::Equal, x(), Box::from(Constant(C::True)));
+        assert_eq!(expr.eval(), 1.0);
+    }
+
+    #[test]
+    fn program() {
+        let expr = Program(String::from("dup *"));
+        assert_eq!(format!("{expr}"), "<< dup * >>");
+        assert_eq!(expr.try_eval().unwrap_err().kind, E::Program);
+        assert_eq!(format!("{}", Program(String::new())), "<< >>");
+    }
+
+    #[test]
+    fn function() {
+        let expr = Function(F::Hypot, vec![Number(Float(3.0)), Number(Float(4.0))]);
+        assert_eq!(expr.eval(), 5.0);
+        assert_eq!(format!("{expr}"), "3 4 hypot");
+
+        let expr = Function(
+            F::Max,
+            vec![Number(Float(1.0)), Number(Float(3.0)), Number(Float(2.0))],
+        );
+        assert_eq!(expr.eval(), 3.0);
+        assert_eq!(format!("{expr}"), "1 3 2 max:3");
+    }
+
+    #[test]
+    fn constant() {
+        let expr = Multiply(Box::from(Number(Float(2.0))), Box::from(Constant(C::Pi)));
+        assert_eq!(expr.eval(), std::f64::consts::TAU);
+        assert_eq!(format!("{expr}"), "2 pi *");
+    }
+
+    #[test]
+    fn variable() {
+        let variables = Variables::from([(String::from("x"), Number(Float(3.0)))]);
+        let context = Context {
+            variables: &variables,
+            ..Context::default()
+        };
+        let expr = Power(
+            Box::from(Variable(String::from("x"))),
+            Box::from(Number(Float(2.0))),
+        );
+        assert_eq!(expr.eval_in(&context), 9.0);
+        assert!(expr.eval().is_nan());
+        assert_eq!(format!("{expr}"), "x 2 ^");
+        assert_eq!(format!("{}", expr.resolve(&variables)), "3 2 ^");
+    }
+
+    #[test]
+    fn call() {
+        let x = || Box::from(Variable(String::from("x")));
+        let definitions = Definitions::from([
+            (
+                String::from("sq"),
+                Definition {
+                    params: vec![String::from("x")],
+                    body: Multiply(x(), x()),
+                },
+            ),
+            (
+                String::from("loop"),
+                Definition {
+                    params: vec![String::from("x")],
+                    body: Call(String::from("loop"), vec![*x()]),
+                },
+            ),
+        ]);
+        let context = Context {
+            definitions: &definitions,
+            ..Context::default()
+        };
+        let expr = Call(
+            String::from("sq"),
+            vec![Call(String::from("sq"), vec![Number(Float(3.0))])],
+        );
+        assert_eq!(expr.eval_in(&context), 81.0);
+        assert!(expr.eval().is_nan());
+        assert_eq!(format!("{expr}"), "3 sq sq");
+        // Recursion stops at the depth limit
+        let expr = Call(String::from("loop"), vec![Number(Float(1.0))]);
+        assert!(expr.eval_in(&context).is_nan());
+        let error = expr.try_eval_in(&context).unwrap_err();
+        assert_eq!(error.kind, E::Recursion);
+        assert_eq!(format!("{}", error.expr), "x loop");
+    }
+
+    #[rstest]
+    #[case(
+        Divide(Box::from(Number(Float(1.0))), Box::from(Number(Float(0.0)))),
+        E::DivisionByZero
+    )]
+    #[case(Power(Box::from(Number(Float(0.0))), Box::from(Number(Float(-1.0)))), E::DivisionByZero)]
+    #[case(Function(F::Mod, vec![Number(Float(1.0)), Number(Float(0.0))]), E::DivisionByZero)]
+    #[case(Sqrt(Box::from(Number(Float(-1.0)))), E::Domain)]
+    #[case(Function(F::Acos, vec![Number(Float(2.0))]), E::Domain)]
+    #[case(Function(F::Ln, vec![Number(Float(0.0))]), E::Domain)]
+    #[case(Function(F::Exp, vec![Number(Float(1000.0))]), E::Overflow)]
+    #[case(
+        Multiply(Box::from(Number(Float(1e300))), Box::from(Number(Float(1e300)))),
+        E::Overflow
+    )]
+    #[case(
+        Subtract(Box::from(Constant(C::Inf)), Box::from(Constant(C::Inf))),
+        E::NotANumber
+    )]
+    #[case(Constant(C::Nan), E::NotANumber)]
+    #[case(Variable(String::from("x")), E::Undefined)]
+    fn try_eval(#[case] expr: crate::calc::expr::Expr, #[case] kind: E) {
+        let error = expr.try_eval().unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.expr.to_string(), expr.to_string());
+        let lenient = Context {
+            policy: Policy::Lenient,
+            ..Context::default()
+        };
+        assert_eq!(expr.try_eval_in(&lenient).is_ok(), kind != E::Undefined);
+    }
+
+    #[test]
+    fn try_eval_subtree() {
+        let expr = Add(
+            Box::from(Number(Float(1.0))),
+            Box::from(Sqrt(Box::from(Negate(Box::from(Number(Float(4.0))))))),
+        );
+        let error = expr.try_eval().unwrap_err();
+        assert_eq!(format!("{}", error.expr), "4 neg sqrt");
+        assert!(expr.eval().is_nan());
+        // Infinity is a number unless an operation overflows into it
+        let expr = Add(Box::from(Number(Float(1.0))), Box::from(Constant(C::Inf)));
+        assert_eq!(expr.try_eval().unwrap(), f64::INFINITY);
+    }
+
     #[test]
     fn undo_number() {
-        let expr = Number(2.0);
+        let expr = Number(Float(2.0));
         let mut undo = expr.undo();
         let x = undo.pop_front();
         assert!(x.is_none());
@@ -120,7 +540,7 @@ mod tests {
 
     #[test]
     fn undo_add() {
-        let expr = Add(Box::from(Number(2.0)), Box::from(Number(1.0)));
+        let expr = Add(Box::from(Number(Float(2.0))), Box::from(Number(Float(1.0))));
         let mut undo = expr.undo();
         let x = undo.pop_front().unwrap();
         let y = undo.pop_front().unwrap();
@@ -130,7 +550,7 @@ mod tests {
 
     #[test]
     fn undo_subtract() {
-        let expr = Subtract(Box::from(Number(2.0)), Box::from(Number(1.0)));
+        let expr = Subtract(Box::from(Number(Float(2.0))), Box::from(Number(Float(1.0))));
         let mut undo = expr.undo();
         let x = undo.pop_front().unwrap();
         let y = undo.pop_front().unwrap();
@@ -140,7 +560,7 @@ mod tests {
 
     #[test]
     fn undo_multiply() {
-        let expr = Multiply(Box::from(Number(2.0)), Box::from(Number(4.0)));
+        let expr = Multiply(Box::from(Number(Float(2.0))), Box::from(Number(Float(4.0))));
         let mut undo = expr.undo();
         let x = undo.pop_front().unwrap();
         let y = undo.pop_front().unwrap();
@@ -150,7 +570,7 @@ mod tests {
 
     #[test]
     fn undo_divide() {
-        let expr = Divide(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Divide(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         let mut undo = expr.undo();
         let x = undo.pop_front().unwrap();
         let y = undo.pop_front().unwrap();
@@ -160,7 +580,7 @@ mod tests {
 
     #[test]
     fn undo_power() {
-        let expr = Power(Box::from(Number(2.0)), Box::from(Number(2.0)));
+        let expr = Power(Box::from(Number(Float(2.0))), Box::from(Number(Float(2.0))));
         let mut undo = expr.undo();
         let x = undo.pop_front().unwrap();
         let y = undo.pop_front().unwrap();
@@ -170,9 +590,28 @@ mod tests {
 
     #[test]
     fn undo_sqrt() {
-        let expr = Sqrt(Box::from(Number(25.0)));
+        let expr = Sqrt(Box::from(Number(Float(25.0))));
         let mut undo = expr.undo();
         let x = undo.pop_front().unwrap();
         assert_eq!(x.eval(), 25.0);
     }
+
+    #[test]
+    fn undo_function() {
+        let expr = Function(
+            F::Min,
+            vec![Number(Float(3.0)), Number(Float(1.0)), Number(Float(2.0))],
+        );
+        let undo = expr.undo();
+        let args: Vec<_> = undo.iter().map(|x| x.eval()).collect();
+        assert_eq!(args, vec![3.0, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn undo_negate() {
+        let expr = Negate(Box::from(Number(Float(3.0))));
+        let mut undo = expr.undo();
+        let x = undo.pop_front().unwrap();
+        assert_eq!(x.eval(), 3.0);
+    }
 }
@@ -0,0 +1,327 @@
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+use crate::calc::context::Context;
+
+/// Named functions taking one or more arguments, called as `name(x, y)` in infix notation and
+/// taking their arguments from the stack in postfix notation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Function {
+    // Trigonometry
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    // Logarithms and exponentials
+    Ln,
+    Log,
+    Log10,
+    Log2,
+    Exp,
+    // Rounding
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Trunc,
+    Sign,
+    // Others
+    Max,
+    Min,
+    Mod,
+    Hypot,
+    // Complex numbers
+    Re,
+    Im,
+    Conj,
+    Arg,
+    // Number theory
+    Gcd,
+    Lcm,
+    Modpow,
+    Modinv,
+    Isprime,
+    Nextprime,
+    // Bitwise operations, which are also infix operators
+    And,
+    Or,
+    Xor,
+    Not,
+    Shl,
+    Shr,
+    Rol,
+    Ror,
+}
+
+impl Function {
+    pub const ALL: [Function; 45] = [
+        Function::Sin,
+        Function::Cos,
+        Function::Tan,
+        Function::Asin,
+        Function::Acos,
+        Function::Atan,
+        Function::Sinh,
+        Function::Cosh,
+        Function::Tanh,
+        Function::Asinh,
+        Function::Acosh,
+        Function::Atanh,
+        Function::Ln,
+        Function::Log,
+        Function::Log10,
+        Function::Log2,
+        Function::Exp,
+        Function::Abs,
+        Function::Floor,
+        Function::Ceil,
+        Function::Round,
+        Function::Trunc,
+        Function::Sign,
+        Function::Max,
+        Function::Min,
+        Function::Mod,
+        Function::Hypot,
+        Function::Re,
+        Function::Im,
+        Function::Conj,
+        Function::Arg,
+        Function::Gcd,
+        Function::Lcm,
+        Function::Modpow,
+        Function::Modinv,
+        Function::Isprime,
+        Function::Nextprime,
+        Function::And,
+        Function::Or,
+        Function::Xor,
+        Function::Not,
+        Function::Shl,
+        Function::Shr,
+        Function::Rol,
+        Function::Ror,
+    ];
+
+    pub fn new(name: &str) -> Option<Function> {
+        Function::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Tan => "tan",
+            Function::Asin => "asin",
+            Function::Acos => "acos",
+            Function::Atan => "atan",
+            Function::Sinh => "sinh",
+            Function::Cosh => "cosh",
+            Function::Tanh => "tanh",
+            Function::Asinh => "asinh",
+            Function::Acosh => "acosh",
+            Function::Atanh => "atanh",
+            Function::Ln => "ln",
+            Function::Log => "log",
+            Function::Log10 => "log10",
+            Function::Log2 => "log2",
+            Function::Exp => "exp",
+            Function::Abs => "abs",
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::Round => "round",
+            Function::Trunc => "trunc",
+            Function::Sign => "sign",
+            Function::Max => "max",
+            Function::Min => "min",
+            Function::Mod => "mod",
+            Function::Hypot => "hypot",
+            Function::Re => "re",
+            Function::Im => "im",
+            Function::Conj => "conj",
+            Function::Arg => "arg",
+            Function::Gcd => "gcd",
+            Function::Lcm => "lcm",
+            Function::Modpow => "modpow",
+            Function::Modinv => "modinv",
+            Function::Isprime => "isprime",
+            Function::Nextprime => "nextprime",
+            Function::And => "and",
+            Function::Or => "or",
+            Function::Xor => "xor",
+            Function::Not => "not",
+            Function::Shl => "shl",
+            Function::Shr => "shr",
+            Function::Rol => "rol",
+            Function::Ror => "ror",
+        }
+    }
+
+    /// How many arguments the function accepts. Postfix notation takes the lower bound from the
+    /// stack unless told otherwise, e.g. `1 2 3 max:3`.
+    pub fn arity(&self) -> RangeInclusive<usize> {
+        match self {
+            Function::Max | Function::Min | Function::Gcd | Function::Lcm => 2..=usize::MAX,
+            // Common logarithm, or the logarithm in the base given as second argument
+            Function::Log => 1..=2,
+            Function::Mod
+            | Function::Hypot
+            | Function::Modinv
+            | Function::And
+            | Function::Or
+            | Function::Xor
+            | Function::Shl
+            | Function::Shr
+            | Function::Rol
+            | Function::Ror => 2..=2,
+            Function::Modpow => 3..=3,
+            _ => 1..=1,
+        }
+    }
+
+    /// Whether the function is only defined for integers, which it takes whatever the backend.
+    pub fn takes_integers(&self) -> bool {
+        matches!(
+            self,
+            Function::Gcd
+                | Function::Lcm
+                | Function::Modpow
+                | Function::Modinv
+                | Function::Isprime
+                | Function::Nextprime
+                | Function::And
+                | Function::Or
+                | Function::Xor
+                | Function::Not
+                | Function::Shl
+                | Function::Shr
+                | Function::Rol
+                | Function::Ror
+        )
+    }
+
+    pub fn apply(&self, args: &[f64], context: &Context) -> f64 {
+        let angle = context.angle;
+        match (self, args) {
+            (Function::Sin, [x]) => angle.to_radians(*x).sin(),
+            (Function::Cos, [x]) => angle.to_radians(*x).cos(),
+            (Function::Tan, [x]) => angle.to_radians(*x).tan(),
+            (Function::Asin, [x]) => angle.from_radians(x.asin()),
+            (Function::Acos, [x]) => angle.from_radians(x.acos()),
+            (Function::Atan, [x]) => angle.from_radians(x.atan()),
+            (Function::Sinh, [x]) => x.sinh(),
+            (Function::Cosh, [x]) => x.cosh(),
+            (Function::Tanh, [x]) => x.tanh(),
+            (Function::Asinh, [x]) => x.asinh(),
+            (Function::Acosh, [x]) => x.acosh(),
+            (Function::Atanh, [x]) => x.atanh(),
+            (Function::Ln, [x]) => x.ln(),
+            (Function::Log | Function::Log10, [x]) => x.log10(),
+            (Function::Log, [x, base]) => x.log(*base),
+            (Function::Log2, [x]) => x.log2(),
+            (Function::Exp, [x]) => x.exp(),
+            (Function::Abs, [x]) => x.abs(),
+            (Function::Floor, [x]) => x.floor(),
+            (Function::Ceil, [x]) => x.ceil(),
+            (Function::Round, [x]) => x.round(),
+            (Function::Trunc, [x]) => x.trunc(),
+            // Unlike f64::signum, zero has no sign
+            (Function::Sign, [x]) if *x == 0.0 => 0.0,
+            (Function::Sign, [x]) => x.signum(),
+            (Function::Max, args) => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            (Function::Min, args) => args.iter().copied().fold(f64::INFINITY, f64::min),
+            // Floored division, so the result takes the sign of the divisor
+            (Function::Mod, [x, y]) => x - y * (x / y).floor(),
+            (Function::Hypot, [x, y]) => x.hypot(*y),
+            // Real numbers as complex numbers with no imaginary part
+            (Function::Re | Function::Conj, [x]) => *x,
+            (Function::Im, [_]) => 0.0,
+            (Function::Arg, [x]) => angle.from_radians(0.0_f64.atan2(*x)),
+            _ => f64::NAN,
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::calc::context::Angle;
+
+    #[rstest]
+    #[case(Function::Max, &[1.0, 3.0, 2.0], 3.0)]
+    #[case(Function::Min, &[1.0, 3.0, -2.0], -2.0)]
+    #[case(Function::Log, &[100.0], 2.0)]
+    #[case(Function::Log, &[8.0, 2.0], 3.0)]
+    #[case(Function::Hypot, &[3.0, 4.0], 5.0)]
+    #[case(Function::Sin, &[0.0], 0.0)]
+    #[case(Function::Cos, &[0.0], 1.0)]
+    #[case(Function::Tan, &[0.0], 0.0)]
+    #[case(Function::Asin, &[1.0], std::f64::consts::FRAC_PI_2)]
+    #[case(Function::Acos, &[1.0], 0.0)]
+    #[case(Function::Atan, &[0.0], 0.0)]
+    #[case(Function::Sinh, &[0.0], 0.0)]
+    #[case(Function::Cosh, &[0.0], 1.0)]
+    #[case(Function::Tanh, &[0.0], 0.0)]
+    #[case(Function::Asinh, &[0.0], 0.0)]
+    #[case(Function::Acosh, &[1.0], 0.0)]
+    #[case(Function::Atanh, &[0.0], 0.0)]
+    #[case(Function::Ln, &[1.0], 0.0)]
+    #[case(Function::Log10, &[1000.0], 3.0)]
+    #[case(Function::Log2, &[8.0], 3.0)]
+    #[case(Function::Exp, &[0.0], 1.0)]
+    #[case(Function::Abs, &[-2.5], 2.5)]
+    #[case(Function::Floor, &[-2.5], -3.0)]
+    #[case(Function::Ceil, &[-2.5], -2.0)]
+    #[case(Function::Round, &[2.5], 3.0)]
+    #[case(Function::Trunc, &[-2.5], -2.0)]
+    #[case(Function::Sign, &[-2.5], -1.0)]
+    #[case(Function::Sign, &[0.0], 0.0)]
+    #[case(Function::Mod, &[7.0, 3.0], 1.0)]
+    #[case(Function::Mod, &[-7.0, 3.0], 2.0)]
+    #[case(Function::Mod, &[7.0, -3.0], -2.0)]
+    #[case(Function::Re, &[-2.5], -2.5)]
+    #[case(Function::Im, &[-2.5], 0.0)]
+    #[case(Function::Arg, &[-2.5], std::f64::consts::PI)]
+    #[case(Function::Arg, &[2.5], 0.0)]
+    fn should_apply(#[case] function: Function, #[case] args: &[f64], #[case] result: f64) {
+        assert_eq!(function.apply(args, &Context::default()), result);
+    }
+
+    #[rstest]
+    #[case(Function::Sin, 90.0, 1.0)]
+    #[case(Function::Cos, 180.0, -1.0)]
+    #[case(Function::Asin, 1.0, 90.0)]
+    #[case(Function::Atan, 0.0, 0.0)]
+    fn should_apply_in_degrees(#[case] function: Function, #[case] x: f64, #[case] result: f64) {
+        let context = Context {
+            angle: Angle::Degrees,
+            ..Context::default()
+        };
+        assert_eq!(function.apply(&[x], &context), result);
+    }
+
+    #[test]
+    fn should_name() {
+        for function in Function::ALL {
+            assert_eq!(Function::new(function.name()), Some(function));
+        }
+    }
+}
@@ -0,0 +1,781 @@
+mod complex;
+mod decimal;
+mod integer;
+mod rational;
+
+use std::cmp::Ordering;
+use std::fmt::{Debug, Display, Formatter};
+
+use bigdecimal::BigDecimal;
+use num::complex::Complex64;
+use num::{BigInt, BigRational, Num, Signed};
+use serde::{Deserialize, Serialize};
+
+use crate::calc::context::{Angle, Context};
+use crate::calc::error::EvalErrorKind;
+use crate::calc::expr::Comparison;
+use crate::calc::function::Function;
+
+/// Largest integer power computed exactly, past which the digits would not fit in memory.
+const MAX_EXPONENT: i64 = 100_000;
+
+/// Arithmetic of a numeric backend, which expressions are evaluated with.
+///
+/// Operations fail with the reason the backend has no value for their result. Floats never do,
+/// as they have infinities and NaN instead.
+pub trait Number: Sized + Clone + PartialEq + Debug + Display {
+    /// Reads a numeric literal without digit separators, like `1.5e3`.
+    fn parse(literal: &str) -> Option<Self>;
+    /// The value closest to a float, if the backend has one.
+    fn from_f64(x: f64) -> Option<Self>;
+    fn to_f64(&self) -> f64;
+
+    fn add(&self, y: &Self) -> Result<Self, EvalErrorKind>;
+    fn sub(&self, y: &Self) -> Result<Self, EvalErrorKind>;
+    fn mul(&self, y: &Self) -> Result<Self, EvalErrorKind>;
+    fn neg(&self) -> Result<Self, EvalErrorKind>;
+    // Results which may not be exact, rounded as the context says
+    fn div(&self, y: &Self, context: &Context) -> Result<Self, EvalErrorKind>;
+    fn pow(&self, y: &Self, context: &Context) -> Result<Self, EvalErrorKind>;
+
+    fn sqrt(&self, _context: &Context) -> Result<Self, EvalErrorKind> {
+        Self::from_f64(self.to_f64().sqrt()).ok_or(EvalErrorKind::Domain)
+    }
+
+    /// Division with the quotient rounded towards zero, as `//`.
+    fn quotient(&self, y: &Self) -> Result<Self, EvalErrorKind> {
+        Self::from_f64((self.to_f64() / y.to_f64()).trunc()).ok_or(EvalErrorKind::Domain)
+    }
+
+    /// Remainder of [`Number::quotient`], taking the sign of the dividend, as `rem`.
+    fn remainder(&self, y: &Self) -> Result<Self, EvalErrorKind> {
+        Self::from_f64(self.to_f64() % y.to_f64()).ok_or(EvalErrorKind::Domain)
+    }
+
+    /// Applies a named function, by default to the floats closest to the arguments.
+    fn apply(function: Function, args: &[Self], context: &Context) -> Result<Self, EvalErrorKind> {
+        approximate(function, args, context)
+    }
+
+    fn is_zero(&self) -> bool;
+    fn is_negative(&self) -> bool;
+
+    fn is_nan(&self) -> bool {
+        false
+    }
+
+    fn is_finite(&self) -> bool {
+        true
+    }
+}
+
+/// Coefficients of the Lanczos approximation of the gamma function, for g = 7 and n = 9.
+const LANCZOS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// The factorial of a float, as the product of the numbers up to it for small integers, or as
+/// the gamma function of the next number, which has poles at the negative integers.
+fn factorial(x: f64) -> f64 {
+    if x.fract() == 0.0 && (0.0..=170.0).contains(&x) {
+        return (2..=x as u32).map(f64::from).product();
+    }
+    if x.fract() == 0.0 && x < 0.0 {
+        return f64::NAN;
+    }
+    if x >= 171.0 {
+        return f64::INFINITY;
+    }
+    // Reflection formula, as the approximation only holds on the right half-plane
+    if x < -0.5 {
+        let pi = std::f64::consts::PI;
+        return -pi / ((pi * x).sin() * factorial(-x - 1.0));
+    }
+    let t = x + 7.5;
+    let sum = LANCZOS[0]
+        + (1..LANCZOS.len())
+            .map(|i| LANCZOS[i] / (x + i as f64))
+            .sum::<f64>();
+    // Split power, which would overflow before the exponential brings it back in range
+    let power = t.powf((x + 0.5) / 2.0);
+    (2.0 * std::f64::consts::PI).sqrt() * power * ((-t).exp() * power) * sum
+}
+
+/// Applies a named function to the floats closest to the arguments.
+fn approximate<N: Number>(
+    function: Function,
+    args: &[N],
+    context: &Context,
+) -> Result<N, EvalErrorKind> {
+    let args: Vec<f64> = args.iter().map(Number::to_f64).collect();
+    N::from_f64(function.apply(&args, context)).ok_or(EvalErrorKind::Domain)
+}
+
+impl Number for f64 {
+    /// Reads finite literals only, since `1e400` would otherwise be infinity.
+    fn parse(literal: &str) -> Option<f64> {
+        literal.parse().ok().filter(|x: &f64| x.is_finite())
+    }
+
+    fn from_f64(x: f64) -> Option<f64> {
+        Some(x)
+    }
+
+    fn to_f64(&self) -> f64 {
+        *self
+    }
+
+    fn add(&self, y: &f64) -> Result<f64, EvalErrorKind> {
+        Ok(self + y)
+    }
+
+    fn sub(&self, y: &f64) -> Result<f64, EvalErrorKind> {
+        Ok(self - y)
+    }
+
+    fn mul(&self, y: &f64) -> Result<f64, EvalErrorKind> {
+        Ok(self * y)
+    }
+
+    fn neg(&self) -> Result<f64, EvalErrorKind> {
+        Ok(-self)
+    }
+
+    fn div(&self, y: &f64, _context: &Context) -> Result<f64, EvalErrorKind> {
+        Ok(self / y)
+    }
+
+    fn pow(&self, y: &f64, _context: &Context) -> Result<f64, EvalErrorKind> {
+        Ok(self.powf(*y))
+    }
+
+    fn is_zero(&self) -> bool {
+        *self == 0.0
+    }
+
+    fn is_negative(&self) -> bool {
+        *self < 0.0
+    }
+
+    // Spelled out, as the method syntax would pick these very methods
+    fn is_nan(&self) -> bool {
+        f64::is_nan(*self)
+    }
+
+    fn is_finite(&self) -> bool {
+        f64::is_finite(*self)
+    }
+}
+
+/// Numeric backend a [`crate::calc::Calc`] evaluates its expressions with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Backend {
+    /// Binary floating point, as `f64`.
+    #[default]
+    Float,
+    /// Exact base 10 numbers, rounding divisions and square roots to the precision setting.
+    Decimal,
+    /// Exact fractions, falling back to floats for irrational results.
+    Rational,
+    /// Complex numbers of floats, so that the square root of -1 is `i`.
+    Complex,
+    /// Integers of any size, falling back to floats for fractional results.
+    Integer,
+    /// Integers wrapping around the word size, with integer division and bitwise operations.
+    Programmer,
+}
+
+impl Backend {
+    pub const ALL: [Backend; 6] = [
+        Backend::Float,
+        Backend::Decimal,
+        Backend::Rational,
+        Backend::Complex,
+        Backend::Integer,
+        Backend::Programmer,
+    ];
+
+    pub fn new(name: &str) -> Option<Backend> {
+        Backend::ALL.into_iter().find(|b| b.to_string() == name)
+    }
+
+    /// Reads a literal as a number of the backend, or as a complex number if imaginary.
+    /// Integers may be written in base 16, 8 or 2 with the prefixes `0x`, `0o` and `0b`.
+    pub fn parse(&self, literal: &str) -> Option<Value> {
+        if let Some(n) = radix(literal) {
+            return Some(self.from_integer(n));
+        }
+        if literal.ends_with('i') {
+            return Complex64::parse(literal).map(Value::Complex);
+        }
+        match self {
+            Backend::Float => f64::parse(literal).map(Value::Float),
+            Backend::Decimal => BigDecimal::parse(literal).map(Value::Decimal),
+            Backend::Rational => BigRational::parse(literal).map(Value::Rational),
+            Backend::Complex => Complex64::parse(literal).map(Value::Complex),
+            Backend::Integer | Backend::Programmer => BigInt::parse(literal).map(Value::Integer),
+        }
+    }
+
+    pub fn from_integer(&self, n: BigInt) -> Value {
+        match self {
+            Backend::Float => Value::Float(Number::to_f64(&n)),
+            Backend::Decimal => Value::Decimal(BigDecimal::from(n)),
+            Backend::Rational => Value::Rational(BigRational::from_integer(n)),
+            Backend::Complex => Value::Complex(Complex64::new(Number::to_f64(&n), 0.0)),
+            Backend::Integer | Backend::Programmer => Value::Integer(n),
+        }
+    }
+
+    pub fn from_f64(&self, x: f64) -> Option<Value> {
+        match self {
+            Backend::Float => f64::from_f64(x).map(Value::Float),
+            Backend::Decimal => BigDecimal::from_f64(x).map(Value::Decimal),
+            // Constants have no exact fraction
+            Backend::Rational | Backend::Integer | Backend::Programmer => Some(Value::Float(x)),
+            Backend::Complex => Complex64::from_f64(x).map(Value::Complex),
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Backend::Float => write!(f, "float"),
+            Backend::Decimal => write!(f, "decimal"),
+            Backend::Rational => write!(f, "rational"),
+            Backend::Complex => write!(f, "complex"),
+            Backend::Integer => write!(f, "integer"),
+            Backend::Programmer => write!(f, "programmer"),
+        }
+    }
+}
+
+/// A number of any of the backends, dispatching its operations to the [`Number`]
+/// implementation of the backend. Results a backend has no exact value for are computed with
+/// floats instead.
+///
+/// Fractions are written as `7/2`, or as the mixed number `3 1/2` with the `{:#}` format, and
+/// complex numbers as `3+4i`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    Decimal(BigDecimal),
+    #[serde(with = "rational")]
+    Rational(BigRational),
+    /// Serialized as the pair of its real and imaginary parts.
+    Complex(Complex64),
+    #[serde(with = "integer")]
+    Integer(BigInt),
+    /// Kept as a bare number, the way it was serialized before there were other backends,
+    /// which serde requires to come last.
+    #[serde(untagged)]
+    Float(f64),
+}
+
+/// Applies a unary operation of [`Number`] to a value.
+macro_rules! unary {
+    ($x:expr, $op:ident $(, $context:expr)?) => {
+        match $x {
+            Value::Decimal(x) => Number::$op(x $(, $context)?).map(Value::Decimal),
+            Value::Rational(x) => Number::$op(x $(, $context)?).map(Value::Rational),
+            Value::Complex(x) => Number::$op(x $(, $context)?).map(Value::Complex),
+            Value::Integer(x) => Number::$op(x $(, $context)?).map(Value::Integer),
+            Value::Float(x) => Number::$op(x $(, $context)?).map(Value::Float),
+        }
+        .or_else(|kind| inexact(kind, || Number::$op(&$x.to_f64() $(, $context)?)))
+    };
+}
+
+/// Applies a binary operation of [`Number`] to two values of the same backend, to complex
+/// numbers if either is one, or to the floats closest to values of different backends.
+macro_rules! binary {
+    ($x:expr, $y:expr, $op:ident $(, $context:expr)?) => {
+        match ($x, $y) {
+            (Value::Decimal(x), Value::Decimal(y)) => {
+                Number::$op(x, y $(, $context)?).map(Value::Decimal)
+            }
+            (Value::Rational(x), Value::Rational(y)) => {
+                Number::$op(x, y $(, $context)?).map(Value::Rational)
+            }
+            (Value::Integer(x), Value::Integer(y)) => {
+                Number::$op(x, y $(, $context)?).map(Value::Integer)
+            }
+            (Value::Float(x), Value::Float(y)) => Number::$op(x, y $(, $context)?).map(Value::Float),
+            (Value::Complex(_), _) | (_, Value::Complex(_)) => {
+                Number::$op(&$x.to_complex(), &$y.to_complex() $(, $context)?).map(Value::Complex)
+            }
+            _ => Err(EvalErrorKind::Inexact),
+        }
+        .or_else(|kind| inexact(kind, || Number::$op(&$x.to_f64(), &$y.to_f64() $(, $context)?)))
+    };
+}
+
+/// Reads an integer literal in base 16, 8 or 2, like `-0xFF`.
+fn radix(literal: &str) -> Option<BigInt> {
+    let (sign, digits) = match literal.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, literal.strip_prefix('+').unwrap_or(literal)),
+    };
+    let base = match digits.get(..2)? {
+        "0x" | "0X" => 16,
+        "0o" | "0O" => 8,
+        "0b" | "0B" => 2,
+        _ => return None,
+    };
+    BigInt::from_str_radix(&digits[2..], base)
+        .ok()
+        .map(|n| n * sign)
+}
+
+/// Computes with floats what a backend has no exact value for.
+fn inexact(
+    kind: EvalErrorKind,
+    float: impl FnOnce() -> Result<f64, EvalErrorKind>,
+) -> Result<Value, EvalErrorKind> {
+    match kind {
+        EvalErrorKind::Inexact => float().map(Value::Float),
+        kind => Err(kind),
+    }
+}
+
+impl Value {
+    /// The number a name stands for unless a variable takes it, which is only `i`, the
+    /// imaginary unit, read with every backend like the imaginary literals.
+    pub fn named(name: &str) -> Option<Value> {
+        (name == "i").then(|| Value::Complex(Complex64::i()))
+    }
+
+    pub fn add(&self, y: &Value) -> Result<Value, EvalErrorKind> {
+        binary!(self, y, add)
+    }
+
+    pub fn sub(&self, y: &Value) -> Result<Value, EvalErrorKind> {
+        binary!(self, y, sub)
+    }
+
+    pub fn mul(&self, y: &Value) -> Result<Value, EvalErrorKind> {
+        binary!(self, y, mul)
+    }
+
+    pub fn neg(&self) -> Result<Value, EvalErrorKind> {
+        unary!(self, neg)
+    }
+
+    pub fn div(&self, y: &Value, context: &Context) -> Result<Value, EvalErrorKind> {
+        binary!(self, y, div, context)
+    }
+
+    pub fn pow(&self, y: &Value, context: &Context) -> Result<Value, EvalErrorKind> {
+        // Integer exponents, like the 2 of `x²`, keep decimals and fractions exact
+        if let (Value::Decimal(_) | Value::Rational(_), Value::Integer(n)) = (self, y) {
+            return self.pow(&self.with_integer(n.clone()), context);
+        }
+        binary!(self, y, pow, context)
+    }
+
+    pub fn sqrt(&self, context: &Context) -> Result<Value, EvalErrorKind> {
+        unary!(self, sqrt, context)
+    }
+
+    /// The factorial of a number, exact for natural numbers of the backends with integers,
+    /// and the gamma function of the next number otherwise, like `0.5!` which is `√π/2`.
+    pub fn factorial(&self) -> Result<Value, EvalErrorKind> {
+        if let Value::Decimal(_) | Value::Rational(_) | Value::Integer(_) = self {
+            if let Some(n) = self.to_integer() {
+                if Signed::is_negative(&n) {
+                    return Err(EvalErrorKind::Domain);
+                }
+                if let Some(n) = integer::factorial(&n) {
+                    return Ok(self.with_integer(n));
+                }
+            }
+        }
+        match self {
+            Value::Complex(x) if x.im != 0.0 => Err(EvalErrorKind::Domain),
+            Value::Complex(x) => Ok(Value::Complex(Complex64::new(factorial(x.re), 0.0))),
+            x => Ok(Value::Float(factorial(x.to_f64()))),
+        }
+    }
+
+    /// A hundredth of the number.
+    pub fn percent(&self, context: &Context) -> Result<Value, EvalErrorKind> {
+        self.div(&self.with_integer(BigInt::from(100)), context)
+    }
+
+    /// Whether a comparison holds, as 1 or 0. NaN has no order and is only ever unequal.
+    /// Complex numbers off the real axis can be told equal or not, but not ordered.
+    pub fn compare(&self, comparison: Comparison, y: &Value) -> Result<Value, EvalErrorKind> {
+        let order = match (self, y) {
+            (Value::Float(x), Value::Float(y)) => x.partial_cmp(y),
+            _ => {
+                let difference = self.sub(y)?;
+                let equality = matches!(comparison, Comparison::Equal | Comparison::NotEqual);
+                match difference {
+                    Value::Complex(z) if z.im != 0.0 && equality => None,
+                    Value::Complex(z) if z.im != 0.0 => return Err(EvalErrorKind::Domain),
+                    _ if difference.is_nan() => None,
+                    _ if difference.is_zero() => Some(Ordering::Equal),
+                    _ if difference.is_negative() => Some(Ordering::Less),
+                    _ => Some(Ordering::Greater),
+                }
+            }
+        };
+        let holds = match (comparison, order) {
+            (Comparison::Equal, order) => order == Some(Ordering::Equal),
+            (Comparison::NotEqual, order) => order != Some(Ordering::Equal),
+            (_, None) => false,
+            (Comparison::Less, Some(order)) => order.is_lt(),
+            (Comparison::LessOrEqual, Some(order)) => order.is_le(),
+            (Comparison::Greater, Some(order)) => order.is_gt(),
+            (Comparison::GreaterOrEqual, Some(order)) => order.is_ge(),
+        };
+        Ok(self.with_integer(BigInt::from(u8::from(holds))))
+    }
+
+    pub fn quotient(&self, y: &Value) -> Result<Value, EvalErrorKind> {
+        binary!(self, y, quotient)
+    }
+
+    pub fn remainder(&self, y: &Value) -> Result<Value, EvalErrorKind> {
+        binary!(self, y, remainder)
+    }
+
+    /// Applies a named function to arguments of the same backend, to complex numbers if any
+    /// argument is one, or to the floats closest to them otherwise. Number theory functions
+    /// take integers of any backend.
+    pub fn apply(
+        function: Function,
+        args: &[Value],
+        context: &Context,
+    ) -> Result<Value, EvalErrorKind> {
+        let decimals: Option<Vec<BigDecimal>> = args
+            .iter()
+            .map(|x| match x {
+                Value::Decimal(x) => Some(x.clone()),
+                _ => None,
+            })
+            .collect();
+        let rationals: Option<Vec<BigRational>> = args
+            .iter()
+            .map(|x| match x {
+                Value::Rational(x) => Some(x.clone()),
+                _ => None,
+            })
+            .collect();
+        let integers: Option<Vec<BigInt>> = args
+            .iter()
+            .map(|x| match x {
+                Value::Integer(x) => Some(x.clone()),
+                _ => None,
+            })
+            .collect();
+        if function.takes_integers() {
+            let integers: Option<Vec<BigInt>> = args.iter().map(Value::to_integer).collect();
+            let integers = integers.ok_or(EvalErrorKind::Domain)?;
+            let result = BigInt::apply(function, &integers, context)?;
+            return Ok(args[0].with_integer(result));
+        }
+        if args.iter().any(|x| matches!(x, Value::Complex(_))) {
+            let args: Vec<Complex64> = args.iter().map(Value::to_complex).collect();
+            return Complex64::apply(function, &args, context).map(Value::Complex);
+        }
+        let result = match (decimals, rationals, integers) {
+            (Some(args), _, _) => BigDecimal::apply(function, &args, context).map(Value::Decimal),
+            (_, Some(args), _) => BigRational::apply(function, &args, context).map(Value::Rational),
+            (_, _, Some(args)) => BigInt::apply(function, &args, context).map(Value::Integer),
+            _ => Err(EvalErrorKind::Inexact),
+        };
+        result.or_else(|kind| {
+            let args: Vec<f64> = args.iter().map(Value::to_f64).collect();
+            inexact(kind, || f64::apply(function, &args, context))
+        })
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Value::Decimal(x) => Number::to_f64(x),
+            Value::Rational(x) => Number::to_f64(x),
+            Value::Complex(x) => Number::to_f64(x),
+            Value::Integer(x) => Number::to_f64(x),
+            Value::Float(x) => *x,
+        }
+    }
+
+    /// The integer the value stands for, if it is one.
+    pub fn to_integer(&self) -> Option<BigInt> {
+        match self {
+            Value::Decimal(x) if x.is_integer() => {
+                Some(x.with_scale(0).into_bigint_and_exponent().0)
+            }
+            Value::Rational(x) if x.is_integer() => Some(x.to_integer()),
+            Value::Integer(x) => Some(x.clone()),
+            Value::Decimal(_) | Value::Rational(_) => None,
+            x => BigInt::from_f64(x.to_f64()),
+        }
+    }
+
+    /// An integer as a number of the same backend as the value.
+    fn with_integer(&self, n: BigInt) -> Value {
+        match self {
+            Value::Decimal(_) => Value::Decimal(BigDecimal::from(n)),
+            Value::Rational(_) => Value::Rational(BigRational::from_integer(n)),
+            Value::Complex(_) => Value::Complex(Complex64::new(Number::to_f64(&n), 0.0)),
+            Value::Integer(_) => Value::Integer(n),
+            Value::Float(_) => Value::Float(Number::to_f64(&n)),
+        }
+    }
+
+    /// The prime factors of an integer, with repetitions, as numbers of its backend.
+    pub fn factor(&self) -> Option<Vec<Value>> {
+        let factors = integer::factor(&self.to_integer()?)?;
+        Some(factors.into_iter().map(|n| self.with_integer(n)).collect())
+    }
+
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Value::Complex(x) => *x,
+            x => Complex64::new(x.to_f64(), 0.0),
+        }
+    }
+
+    /// Writes a number as its modulus and argument in the angle unit, like
+    /// `5∠53.13010235415598` for `3+4i`.
+    pub fn to_polar(&self, angle: Angle) -> String {
+        match self {
+            Value::Complex(x) => complex::polar(x, angle),
+            x => complex::polar(&x.to_complex(), angle),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Value::Decimal(x) => Number::is_zero(x),
+            Value::Rational(x) => Number::is_zero(x),
+            Value::Complex(x) => Number::is_zero(x),
+            Value::Integer(x) => Number::is_zero(x),
+            Value::Float(x) => Number::is_zero(x),
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Value::Decimal(x) => Number::is_negative(x),
+            Value::Rational(x) => Number::is_negative(x),
+            Value::Complex(x) => Number::is_negative(x),
+            Value::Integer(x) => Number::is_negative(x),
+            Value::Float(x) => Number::is_negative(x),
+        }
+    }
+
+    pub fn is_nan(&self) -> bool {
+        match self {
+            Value::Decimal(x) => Number::is_nan(x),
+            Value::Rational(x) => Number::is_nan(x),
+            Value::Complex(x) => Number::is_nan(x),
+            Value::Integer(x) => Number::is_nan(x),
+            Value::Float(x) => x.is_nan(),
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Value::Decimal(x) => Number::is_finite(x),
+            Value::Rational(x) => Number::is_finite(x),
+            Value::Complex(x) => Number::is_finite(x),
+            Value::Integer(x) => Number::is_finite(x),
+            Value::Float(x) => x.is_finite(),
+        }
+    }
+}
+
+impl PartialEq<f64> for Value {
+    fn eq(&self, other: &f64) -> bool {
+        self.to_f64() == *other
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Decimal(x) => f.write_str(&decimal::format(x)),
+            Value::Rational(x) if f.alternate() => f.write_str(&rational::mixed(x)),
+            Value::Rational(x) => write!(f, "{}", x),
+            Value::Complex(x) => f.write_str(&complex::rectangular(x)),
+            Value::Integer(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    #[rstest]
+    #[case(Backend::Float, "1.5e3", Some(Value::Float(1500.0)))]
+    #[case(Backend::Float, ".5", Some(Value::Float(0.5)))]
+    #[case(Backend::Float, "x", None)]
+    #[case(Backend::Float, "1e400", None)]
+    #[case(Backend::Complex, "-1e400", None)]
+    #[case(Backend::Integer, "1e400i", None)]
+    fn should_parse(#[case] backend: Backend, #[case] literal: &str, #[case] value: Option<Value>) {
+        assert_eq!(backend.parse(literal), value);
+    }
+
+    #[test]
+    fn should_compute() {
+        let x = Value::Float(3.0);
+        let y = Value::Float(4.0);
+        assert_eq!(x.add(&y).unwrap(), 7.0);
+        assert_eq!(x.sub(&y).unwrap(), -1.0);
+        assert_eq!(x.mul(&y).unwrap(), 12.0);
+        let context = Context::default();
+        assert_eq!(x.div(&y, &context).unwrap(), 0.75);
+        assert_eq!(x.pow(&y, &context).unwrap(), 81.0);
+        assert_eq!(y.sqrt(&context).unwrap(), 2.0);
+        assert_eq!(x.neg().unwrap(), -3.0);
+        let max = Value::apply(Function::Max, &[x, y], &context).unwrap();
+        assert_eq!(max, 4.0);
+    }
+
+    #[rstest]
+    #[case(Function::Abs, &["-9223372036854775808"], "9223372036854775808")]
+    #[case(Function::Abs, &["-1267650600228229401496703205376"], "1267650600228229401496703205376")]
+    #[case(Function::Mod, &["1267650600228229401496703205377", "7"], "3")]
+    #[case(Function::Mod, &["-9007199254740993", "9007199254740992"], "9007199254740991")]
+    #[case(Function::Max, &["9007199254740993", "9007199254740992"], "9007199254740993")]
+    #[case(Function::Min, &["9007199254740993", "9007199254740995"], "9007199254740993")]
+    fn should_apply_to_integers(
+        #[case] function: Function,
+        #[case] args: &[&str],
+        #[case] result: &str,
+    ) {
+        let args: Vec<Value> = args
+            .iter()
+            .map(|x| Value::Integer(x.parse().unwrap()))
+            .collect();
+        let value = Value::apply(function, &args, &Context::default()).unwrap();
+        assert_eq!(value, Value::Integer(result.parse().unwrap()));
+    }
+
+    #[rstest]
+    #[case(Value::Float(5.0), Value::Float(120.0))]
+    #[case(Value::Float(0.0), Value::Float(1.0))]
+    #[case(
+        Value::Integer(BigInt::from(25)),
+        Value::Integer("15511210043330985984000000".parse().unwrap())
+    )]
+    #[case(
+        Value::Rational(BigRational::from_integer(BigInt::from(4))),
+        Value::Rational(BigRational::from_integer(BigInt::from(24)))
+    )]
+    #[case(
+        Value::Complex(Complex64::new(3.0, 0.0)),
+        Value::Complex(Complex64::new(6.0, 0.0))
+    )]
+    fn should_factorial(#[case] x: Value, #[case] factorial: Value) {
+        assert_eq!(x.factorial(), Ok(factorial));
+    }
+
+    #[rstest]
+    #[case(0.5, std::f64::consts::PI.sqrt() / 2.0)]
+    #[case(-0.5, std::f64::consts::PI.sqrt())]
+    #[case(2.5, 3.323_350_970_447_843)]
+    #[case(-1.5, -2.0 * std::f64::consts::PI.sqrt())]
+    #[case(170.5, 9.483_367_566_824_801e307)]
+    fn should_gamma(#[case] x: f64, #[case] factorial: f64) {
+        let error = (super::factorial(x) - factorial).abs() / factorial.abs();
+        assert!(error < 1e-12, "{}! = {}", x, super::factorial(x));
+    }
+
+    #[test]
+    fn should_not_factorial() {
+        assert_eq!(
+            Value::Integer(BigInt::from(-1)).factorial(),
+            Err(EvalErrorKind::Domain)
+        );
+        let z = Value::Complex(Complex64::new(0.0, 1.0));
+        assert_eq!(z.factorial(), Err(EvalErrorKind::Domain));
+        assert!(super::factorial(-2.0).is_nan());
+        assert_eq!(super::factorial(171.0), f64::INFINITY);
+        let x = Value::Decimal(BigDecimal::from(20_000));
+        assert_eq!(x.factorial(), Ok(Value::Float(f64::INFINITY)));
+    }
+
+    #[rstest]
+    #[case(Value::Float(1.0), Comparison::Less, Value::Float(2.0), true)]
+    #[case(
+        Value::Float(f64::NAN),
+        Comparison::Equal,
+        Value::Float(f64::NAN),
+        false
+    )]
+    #[case(Value::Float(f64::NAN), Comparison::NotEqual, Value::Float(1.0), true)]
+    #[case(
+        Value::Float(f64::INFINITY),
+        Comparison::Equal,
+        Value::Float(f64::INFINITY),
+        true
+    )]
+    #[case(
+        Value::Decimal("0.1".parse().unwrap()),
+        Comparison::GreaterOrEqual,
+        Value::Decimal("0.10".parse().unwrap()),
+        true
+    )]
+    #[case(
+        Value::Rational(BigRational::new(BigInt::from(1), BigInt::from(3))),
+        Comparison::Greater,
+        Value::Rational(BigRational::new(BigInt::from(1), BigInt::from(4))),
+        true
+    )]
+    #[case(
+        Value::Integer(BigInt::from(-3)),
+        Comparison::LessOrEqual,
+        Value::Integer(BigInt::from(-4)),
+        false
+    )]
+    #[case(
+        Value::Complex(Complex64::new(1.0, 1.0)),
+        Comparison::NotEqual,
+        Value::Complex(Complex64::new(2.0, 0.0)),
+        true
+    )]
+    fn should_compare(
+        #[case] x: Value,
+        #[case] comparison: Comparison,
+        #[case] y: Value,
+        #[case] holds: bool,
+    ) {
+        let value = x.compare(comparison, &y).unwrap();
+        assert_eq!(value.is_zero(), !holds);
+        assert_eq!(value.to_f64(), f64::from(u8::from(holds)));
+    }
+
+    #[rstest]
+    #[case(Comparison::Less)]
+    #[case(Comparison::GreaterOrEqual)]
+    fn should_not_order_complex_numbers(#[case] comparison: Comparison) {
+        let x = Value::Complex(Complex64::new(0.0, 1.0));
+        let y = Value::Complex(Complex64::new(0.0, 2.0));
+        assert_eq!(x.compare(comparison, &y), Err(EvalErrorKind::Domain));
+    }
+
+    #[test]
+    fn should_serialize_floats_bare() {
+        let json = "1.5";
+        let value: Value = serde_json::from_str(json).unwrap();
+        assert_eq!(value, Value::Float(1.5));
+        assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    }
+}
@@ -0,0 +1,248 @@
+use num::complex::Complex64;
+use num::Zero;
+
+use crate::calc::context::{Angle, Context};
+use crate::calc::error::EvalErrorKind;
+use crate::calc::function::Function;
+use crate::calc::number::Number;
+
+/// Whether a complex number lies on the real axis.
+fn is_real(z: &Complex64) -> bool {
+    z.im == 0.0
+}
+
+impl Number for Complex64 {
+    /// Reads real literals, and imaginary ones ending with `i` like `2i`.
+    fn parse(literal: &str) -> Option<Complex64> {
+        match literal.strip_suffix('i') {
+            Some(literal) => f64::parse(literal).map(|im| Complex64::new(0.0, im)),
+            None => f64::parse(literal).map(|re| Complex64::new(re, 0.0)),
+        }
+    }
+
+    fn from_f64(x: f64) -> Option<Complex64> {
+        Some(Complex64::new(x, 0.0))
+    }
+
+    /// The real part, or NaN if there is an imaginary one.
+    fn to_f64(&self) -> f64 {
+        if is_real(self) {
+            self.re
+        } else {
+            f64::NAN
+        }
+    }
+
+    fn add(&self, y: &Complex64) -> Result<Complex64, EvalErrorKind> {
+        Ok(self + y)
+    }
+
+    fn sub(&self, y: &Complex64) -> Result<Complex64, EvalErrorKind> {
+        Ok(self - y)
+    }
+
+    fn mul(&self, y: &Complex64) -> Result<Complex64, EvalErrorKind> {
+        Ok(self * y)
+    }
+
+    // Without a negative zero imaginary part, which would put `4 neg` on the other side of
+    // the branch cut of square roots and logarithms
+    fn neg(&self) -> Result<Complex64, EvalErrorKind> {
+        Ok(Complex64::new(-self.re, -self.im + 0.0))
+    }
+
+    fn div(&self, y: &Complex64, _context: &Context) -> Result<Complex64, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok(self / y)
+    }
+
+    /// Stays on the real axis for powers of positive reals and integer powers, rather than
+    /// picking up rounding errors in the imaginary part.
+    fn pow(&self, y: &Complex64, _context: &Context) -> Result<Complex64, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Ok(Complex64::new(1.0, 0.0));
+        }
+        if Zero::is_zero(self) {
+            return match y.re {
+                re if re > 0.0 => Ok(Complex64::zero()),
+                re if re < 0.0 => Err(EvalErrorKind::DivisionByZero),
+                _ => Err(EvalErrorKind::Domain),
+            };
+        }
+        if !is_real(y) {
+            return Ok(self.powc(*y));
+        }
+        let integer = y.re.fract() == 0.0 && y.re.abs() <= i32::MAX as f64;
+        match (is_real(self), integer) {
+            (true, _) if self.re > 0.0 || integer => Ok(Complex64::new(self.re.powf(y.re), 0.0)),
+            (_, true) => Ok(self.powi(y.re as i32)),
+            _ => Ok(self.powf(y.re)),
+        }
+    }
+
+    fn sqrt(&self, _context: &Context) -> Result<Complex64, EvalErrorKind> {
+        Ok(Complex64::sqrt(*self))
+    }
+
+    /// Applies the complex extension of a function, taking and returning angles in the unit
+    /// of the context. Functions only defined for real numbers fail on the others.
+    fn apply(
+        function: Function,
+        args: &[Complex64],
+        context: &Context,
+    ) -> Result<Complex64, EvalErrorKind> {
+        let to_radians = |z: &Complex64| z * context.angle.to_radians(1.0);
+        let from_radians = |z: Complex64| z * context.angle.from_radians(1.0);
+        let real = |x: f64| Ok(Complex64::new(x, 0.0));
+        match (function, args) {
+            (Function::Sin, [z]) => Ok(to_radians(z).sin()),
+            (Function::Cos, [z]) => Ok(to_radians(z).cos()),
+            (Function::Tan, [z]) => Ok(to_radians(z).tan()),
+            (Function::Asin, [z]) => Ok(from_radians(z.asin())),
+            (Function::Acos, [z]) => Ok(from_radians(z.acos())),
+            (Function::Atan, [z]) => Ok(from_radians(z.atan())),
+            (Function::Sinh, [z]) => Ok(z.sinh()),
+            (Function::Cosh, [z]) => Ok(z.cosh()),
+            (Function::Tanh, [z]) => Ok(z.tanh()),
+            (Function::Asinh, [z]) => Ok(z.asinh()),
+            (Function::Acosh, [z]) => Ok(z.acosh()),
+            (Function::Atanh, [z]) => Ok(z.atanh()),
+            (Function::Ln, [z]) => Ok(z.ln()),
+            (Function::Log | Function::Log10, [z]) => Ok(z.log10()),
+            (Function::Log, [z, base]) => Ok(z.ln() / base.ln()),
+            (Function::Log2, [z]) => Ok(z.log2()),
+            (Function::Exp, [z]) => Ok(z.exp()),
+            (Function::Abs, [z]) => real(z.norm()),
+            (Function::Floor, [z]) => Ok(Complex64::new(z.re.floor(), z.im.floor())),
+            (Function::Ceil, [z]) => Ok(Complex64::new(z.re.ceil(), z.im.ceil())),
+            (Function::Round, [z]) => Ok(Complex64::new(z.re.round(), z.im.round())),
+            (Function::Trunc, [z]) => Ok(Complex64::new(z.re.trunc(), z.im.trunc())),
+            // The point of the unit circle in the same direction
+            (Function::Sign, [z]) if Zero::is_zero(z) => Ok(Complex64::zero()),
+            (Function::Sign, [z]) => Ok(z / z.norm()),
+            (Function::Re, [z]) => real(z.re),
+            (Function::Im, [z]) => real(z.im),
+            (Function::Conj, [z]) => Ok(z.conj()),
+            (Function::Arg, [z]) => real(context.angle.from_radians(z.arg())),
+            // Comparisons and remainders, which need an order
+            (_, args) if args.iter().all(is_real) => {
+                let args: Vec<f64> = args.iter().map(|z| z.re).collect();
+                real(function.apply(&args, context))
+            }
+            _ => Err(EvalErrorKind::Domain),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn is_negative(&self) -> bool {
+        is_real(self) && self.re < 0.0
+    }
+
+    fn is_nan(&self) -> bool {
+        Complex64::is_nan(*self)
+    }
+
+    fn is_finite(&self) -> bool {
+        Complex64::is_finite(*self)
+    }
+}
+
+/// Whether a part is only the rounding error of a computation next to the other one, like
+/// the real part of `-1 0.5 ^`.
+fn is_negligible(x: f64, other: f64) -> bool {
+    x.abs() < other.abs() * f64::EPSILON
+}
+
+/// Writes a complex number as `3+4i`, leaving out a zero or negligible part.
+pub fn rectangular(z: &Complex64) -> String {
+    let re = if is_negligible(z.re, z.im) { 0.0 } else { z.re };
+    let im = if is_negligible(z.im, z.re) { 0.0 } else { z.im };
+    match (re, im) {
+        (re, 0.0) => format!("{}", re),
+        (0.0, im) => format!("{}i", im),
+        (re, im) if im < 0.0 => format!("{}-{}i", re, -im),
+        (re, im) => format!("{}+{}i", re, im),
+    }
+}
+
+/// Writes a complex number as its modulus and argument, like `5∠0.9272952180016122`, the
+/// argument being in the angle unit.
+pub fn polar(z: &Complex64, angle: Angle) -> String {
+    let (r, theta) = z.to_polar();
+    format!("{}∠{}", r, angle.from_radians(theta))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn complex(re: f64, im: f64) -> Complex64 {
+        Complex64::new(re, im)
+    }
+
+    #[rstest]
+    #[case("2i", complex(0.0, 2.0))]
+    #[case("1.5e1i", complex(0.0, 15.0))]
+    #[case("-3", complex(-3.0, 0.0))]
+    fn should_parse(#[case] literal: &str, #[case] z: Complex64) {
+        assert_eq!(Complex64::parse(literal), Some(z));
+    }
+
+    #[rstest]
+    #[case(complex(-4.0, 0.0), complex(0.0, 2.0))]
+    #[case(complex(3.0, 4.0), complex(2.0, 1.0))]
+    #[case(complex(0.0, 2.0), complex(1.0, 1.0))]
+    fn should_sqrt(#[case] z: Complex64, #[case] root: Complex64) {
+        assert_eq!(Number::sqrt(&z, &Context::default()), Ok(root));
+    }
+
+    #[rstest]
+    #[case(complex(0.0, 1.0), complex(2.0, 0.0), complex(-1.0, 0.0))]
+    #[case(complex(1.0, 1.0), complex(4.0, 0.0), complex(-4.0, 0.0))]
+    #[case(complex(2.0, 0.0), complex(-1.0, 0.0), complex(0.5, 0.0))]
+    #[case(complex(0.0, 0.0), complex(2.0, 0.0), complex(0.0, 0.0))]
+    fn should_pow(#[case] x: Complex64, #[case] y: Complex64, #[case] power: Complex64) {
+        assert_eq!(x.pow(&y, &Context::default()), Ok(power));
+    }
+
+    #[rstest]
+    #[case(Function::Abs, &[complex(3.0, 4.0)], complex(5.0, 0.0))]
+    #[case(Function::Re, &[complex(3.0, 4.0)], complex(3.0, 0.0))]
+    #[case(Function::Im, &[complex(3.0, 4.0)], complex(4.0, 0.0))]
+    #[case(Function::Conj, &[complex(3.0, 4.0)], complex(3.0, -4.0))]
+    #[case(Function::Arg, &[complex(0.0, 1.0)], complex(std::f64::consts::FRAC_PI_2, 0.0))]
+    #[case(Function::Sign, &[complex(0.0, -2.0)], complex(0.0, -1.0))]
+    #[case(Function::Max, &[complex(1.0, 0.0), complex(2.0, 0.0)], complex(2.0, 0.0))]
+    fn should_apply(#[case] function: Function, #[case] args: &[Complex64], #[case] z: Complex64) {
+        assert_eq!(Complex64::apply(function, args, &Context::default()), Ok(z));
+    }
+
+    #[test]
+    fn should_log_negatives() {
+        let context = Context::default();
+        let z = Complex64::apply(Function::Ln, &[complex(-1.0, 0.0)], &context).unwrap();
+        assert_eq!(z, complex(0.0, std::f64::consts::PI));
+        let args = [complex(1.0, 1.0), complex(2.0, 0.0)];
+        let error = Complex64::apply(Function::Max, &args, &context);
+        assert_eq!(error, Err(EvalErrorKind::Domain));
+    }
+
+    #[rstest]
+    #[case(complex(3.0, 4.0), "3+4i", "5∠53.13010235415598")]
+    #[case(complex(3.0, -4.0), "3-4i", "5∠-53.13010235415598")]
+    #[case(complex(0.0, 2.0), "2i", "2∠90")]
+    #[case(complex(-1.5, 0.0), "-1.5", "1.5∠180")]
+    #[case(complex(6.123233995736766e-17, 1.0), "1i", "1∠90")]
+    #[case(complex(-1.0, 1.2246467991473532e-16), "-1", "1∠180")]
+    fn should_write(#[case] z: Complex64, #[case] rectangular: &str, #[case] polar_form: &str) {
+        assert_eq!(super::rectangular(&z), rectangular);
+        assert_eq!(polar(&z, Angle::Degrees), polar_form);
+    }
+}
@@ -0,0 +1,311 @@
+use std::num::NonZeroU64;
+use std::str::FromStr;
+
+use bigdecimal::{BigDecimal, RoundingMode};
+
+use crate::calc::context::{Context, Precision, Rounding};
+use crate::calc::error::EvalErrorKind;
+use crate::calc::function::Function;
+use crate::calc::number::{approximate, Number, MAX_EXPONENT};
+
+impl From<Rounding> for RoundingMode {
+    fn from(rounding: Rounding) -> Self {
+        match rounding {
+            Rounding::HalfEven => RoundingMode::HalfEven,
+            Rounding::HalfUp => RoundingMode::HalfUp,
+            Rounding::Down => RoundingMode::Down,
+        }
+    }
+}
+
+fn round(x: BigDecimal, precision: &Precision) -> BigDecimal {
+    let digits = NonZeroU64::new(precision.digits).unwrap_or(NonZeroU64::MIN);
+    x.with_precision_round(digits, precision.rounding.into())
+        .normalized()
+}
+
+/// Digits computed past the precision, so that the rounding errors of intermediate results
+/// do not reach the rounded one.
+const GUARD_DIGITS: u64 = 10;
+
+/// Arithmetic carried to the precision and its guard digits.
+fn working(precision: &Precision) -> bigdecimal::Context {
+    let digits = NonZeroU64::new(precision.digits + GUARD_DIGITS).unwrap_or(NonZeroU64::MIN);
+    bigdecimal::Context::default().with_precision(digits)
+}
+
+/// The natural logarithm of a positive number, as `ln m + k ln 10` for `m × 10^k` with a
+/// single integer digit, so that numbers out of the range of floats have one too.
+fn ln(x: &BigDecimal, context: &bigdecimal::Context) -> BigDecimal {
+    let (digits, scale) = x.as_bigint_and_exponent();
+    let k = x.digits() as i64 - 1 - scale;
+    let mantissa = ln_near(&BigDecimal::new(digits, scale + k), context);
+    match k {
+        0 => mantissa,
+        k => mantissa + ln_near(&BigDecimal::from(10), context) * BigDecimal::from(k),
+    }
+}
+
+/// The natural logarithm of a number within the range of floats, refining the float one with
+/// Halley's method, which triples the correct digits at each step. The steps stop once they
+/// only change the guard digits.
+fn ln_near(x: &BigDecimal, context: &bigdecimal::Context) -> BigDecimal {
+    let mut z = BigDecimal::from_f64(x.to_f64().ln()).unwrap_or_default();
+    let digits = context.precision().get() - GUARD_DIGITS / 2;
+    let tolerance = BigDecimal::new(1.into(), i64::try_from(digits).unwrap_or(i64::MAX));
+    for _ in 0..digits {
+        let e = z.exp_with_context(context);
+        let step = context.multiply(&((x - &e) * 2), &context.invert(&(x + &e)));
+        z = context.round_decimal(z + &step);
+        if step.abs() < tolerance {
+            break;
+        }
+    }
+    z
+}
+
+/// Writes a decimal with the same exponent letter whatever its sign, which the bigdecimal
+/// crate writes `e` when positive and `E` when negative.
+pub fn format(x: &BigDecimal) -> String {
+    x.to_string().replace('E', "e")
+}
+
+impl Number for BigDecimal {
+    /// Reads decimal literals with exponents up to [`MAX_EXPONENT`], since adding `1e10000000`
+    /// to `1` would write out all of its digits.
+    fn parse(literal: &str) -> Option<BigDecimal> {
+        let x = BigDecimal::from_str(literal).ok()?;
+        (x.fractional_digit_count().abs() <= MAX_EXPONENT).then_some(x)
+    }
+
+    // From the shortest digits reading back as the float, so that 0.1 is not
+    // 0.1000000000000000055511151231257827
+    fn from_f64(x: f64) -> Option<BigDecimal> {
+        if !x.is_finite() {
+            return None;
+        }
+        BigDecimal::from_str(&x.to_string()).ok()
+    }
+
+    fn to_f64(&self) -> f64 {
+        bigdecimal::ToPrimitive::to_f64(self).unwrap_or(f64::NAN)
+    }
+
+    // Results drop trailing zeros, so that 200 + 10% is 220 rather than 220.0
+    fn add(&self, y: &BigDecimal) -> Result<BigDecimal, EvalErrorKind> {
+        Ok((self + y).normalized())
+    }
+
+    fn sub(&self, y: &BigDecimal) -> Result<BigDecimal, EvalErrorKind> {
+        Ok((self - y).normalized())
+    }
+
+    fn mul(&self, y: &BigDecimal) -> Result<BigDecimal, EvalErrorKind> {
+        Ok((self * y).normalized())
+    }
+
+    fn neg(&self) -> Result<BigDecimal, EvalErrorKind> {
+        Ok(-self)
+    }
+
+    fn div(&self, y: &BigDecimal, context: &Context) -> Result<BigDecimal, EvalErrorKind> {
+        if y.is_zero() {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok(round(self / y, &context.precision))
+    }
+
+    /// Rounded to the precision, through the logarithm for fractional exponents.
+    fn pow(&self, y: &BigDecimal, context: &Context) -> Result<BigDecimal, EvalErrorKind> {
+        let working = working(&context.precision);
+        if !y.is_integer() {
+            if self.is_zero() {
+                return match y.is_negative() {
+                    true => Err(EvalErrorKind::DivisionByZero),
+                    false => Ok(BigDecimal::from(0)),
+                };
+            }
+            if self.is_negative() {
+                return Err(EvalErrorKind::Domain);
+            }
+            let z = working.multiply(y, &ln(self, &working));
+            // Past e^(2 × MAX_EXPONENT), whose digits could not all be written out
+            if z.abs() > 2 * MAX_EXPONENT {
+                return match z.is_negative() {
+                    true => Ok(BigDecimal::from(0)),
+                    false => Err(EvalErrorKind::Overflow),
+                };
+            }
+            return Ok(round(z.exp_with_context(&working), &context.precision));
+        }
+        let n = bigdecimal::ToPrimitive::to_i64(y).filter(|n| n.abs() <= MAX_EXPONENT);
+        match n {
+            None => Err(EvalErrorKind::Overflow),
+            Some(n) if n >= 0 => Ok(round(
+                self.powi_with_context(n, &working),
+                &context.precision,
+            )),
+            Some(_) if self.is_zero() => Err(EvalErrorKind::DivisionByZero),
+            Some(n) => BigDecimal::from(1).div(&self.powi(-n), context),
+        }
+    }
+
+    fn sqrt(&self, context: &Context) -> Result<BigDecimal, EvalErrorKind> {
+        let Precision { digits, rounding } = context.precision;
+        let rounding = bigdecimal::Context::default()
+            .with_prec(digits.max(1))
+            .ok_or(EvalErrorKind::Domain)?
+            .with_rounding_mode(rounding.into());
+        let root = self.sqrt_with_context(&rounding);
+        root.map(|x| x.normalized()).ok_or(EvalErrorKind::Domain)
+    }
+
+    fn quotient(&self, y: &BigDecimal) -> Result<BigDecimal, EvalErrorKind> {
+        if y.is_zero() {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok((self / y).with_scale_round(0, RoundingMode::Down))
+    }
+
+    fn remainder(&self, y: &BigDecimal) -> Result<BigDecimal, EvalErrorKind> {
+        Ok(self - y * self.quotient(y)?)
+    }
+
+    /// Exact for the functions which only take digits away, approximated with floats otherwise.
+    fn apply(
+        function: Function,
+        args: &[BigDecimal],
+        context: &Context,
+    ) -> Result<BigDecimal, EvalErrorKind> {
+        let integer = |x: &BigDecimal, mode| x.with_scale_round(0, mode);
+        match (function, args) {
+            (Function::Abs, [x]) => Ok(x.abs()),
+            (Function::Floor, [x]) => Ok(integer(x, RoundingMode::Floor)),
+            (Function::Ceil, [x]) => Ok(integer(x, RoundingMode::Ceiling)),
+            (Function::Round, [x]) => Ok(integer(x, RoundingMode::HalfUp)),
+            (Function::Trunc, [x]) => Ok(integer(x, RoundingMode::Down)),
+            (Function::Sign, [x]) => Ok(bigdecimal::Signed::signum(x)),
+            (Function::Max, [x, args @ ..]) => Ok(args.iter().fold(x, |m, y| m.max(y)).clone()),
+            (Function::Min, [x, args @ ..]) => Ok(args.iter().fold(x, |m, y| m.min(y)).clone()),
+            (Function::Mod, [_, y]) if y.is_zero() => Err(EvalErrorKind::DivisionByZero),
+            (Function::Mod, [x, y]) => Ok(x - y * integer(&(x / y), RoundingMode::Floor)),
+            _ => approximate(function, args, context),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        bigdecimal::Zero::is_zero(self)
+    }
+
+    fn is_negative(&self) -> bool {
+        bigdecimal::Signed::is_negative(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn decimal(literal: &str) -> BigDecimal {
+        BigDecimal::parse(literal).unwrap()
+    }
+
+    fn context(digits: u64, rounding: Rounding) -> Context<'static> {
+        Context {
+            precision: Precision { digits, rounding },
+            ..Context::default()
+        }
+    }
+
+    #[rstest]
+    #[case("0.1", Some("0.1"))]
+    #[case("-1.5e3", Some("-1500"))]
+    #[case("1e100000", Some("1e+100000"))]
+    #[case("1e100001", None)]
+    #[case("1e-100001", None)]
+    fn should_parse(#[case] literal: &str, #[case] decimal: Option<&str>) {
+        let parsed = BigDecimal::parse(literal).map(|x| x.to_string());
+        assert_eq!(parsed, decimal.map(String::from));
+    }
+
+    #[rstest]
+    #[case("2", "3", 4, Rounding::HalfEven, "0.6667")]
+    #[case("2", "3", 4, Rounding::Down, "0.6666")]
+    #[case("1", "8", 2, Rounding::HalfEven, "0.12")]
+    #[case("1", "8", 2, Rounding::HalfUp, "0.13")]
+    #[case("6", "2", 28, Rounding::HalfEven, "3")]
+    #[case("1", "3", 28, Rounding::HalfEven, "0.3333333333333333333333333333")]
+    fn should_divide(
+        #[case] x: &str,
+        #[case] y: &str,
+        #[case] digits: u64,
+        #[case] rounding: Rounding,
+        #[case] result: &str,
+    ) {
+        let context = context(digits, rounding);
+        let quotient = decimal(x).div(&decimal(y), &context).unwrap();
+        assert_eq!(quotient.to_string(), result);
+    }
+
+    #[rstest]
+    #[case("2", 5, Rounding::HalfEven, "1.4142")]
+    #[case("3", 5, Rounding::HalfUp, "1.7321")]
+    #[case("3", 5, Rounding::Down, "1.732")]
+    #[case("0.0625", 28, Rounding::HalfEven, "0.25")]
+    fn should_sqrt(
+        #[case] x: &str,
+        #[case] digits: u64,
+        #[case] rounding: Rounding,
+        #[case] result: &str,
+    ) {
+        let context = context(digits, rounding);
+        let root = Number::sqrt(&decimal(x), &context).unwrap();
+        assert_eq!(root.to_string(), result);
+    }
+
+    #[rstest]
+    #[case("1.1", "2", "1.21")]
+    #[case("2", "-2", "0.25")]
+    #[case("4", "0.5", "2")]
+    #[case("2", "0.5", "1.414213562373095048801688724")]
+    #[case("10", "-1.5", "0.03162277660168379331998893544")]
+    #[case("2", "1000", "1071508607186267320948425049e+274")]
+    #[case("1e1000", "0.5", "1e+500")]
+    #[case("0.5", "0.5", "0.7071067811865475244008443621")]
+    fn should_pow(#[case] x: &str, #[case] y: &str, #[case] result: &str) {
+        let power = decimal(x).pow(&decimal(y), &Context::default()).unwrap();
+        assert_eq!(power.to_string(), result);
+    }
+
+    #[rstest]
+    #[case(Function::Round, &["2.5"], "3")]
+    #[case(Function::Round, &["-2.5"], "-3")]
+    #[case(Function::Floor, &["-2.5"], "-3")]
+    #[case(Function::Ceil, &["-2.5"], "-2")]
+    #[case(Function::Trunc, &["-2.5"], "-2")]
+    #[case(Function::Abs, &["-0.1"], "0.1")]
+    #[case(Function::Sign, &["-0.1"], "-1")]
+    #[case(Function::Max, &["0.1", "0.3", "0.2"], "0.3")]
+    #[case(Function::Min, &["0.1", "0.3", "0.2"], "0.1")]
+    #[case(Function::Mod, &["-7.5", "2"], "0.5")]
+    #[case(Function::Log, &["100"], "2")]
+    fn should_apply(#[case] function: Function, #[case] args: &[&str], #[case] result: &str) {
+        let args: Vec<BigDecimal> = args.iter().map(|x| decimal(x)).collect();
+        let value = BigDecimal::apply(function, &args, &Context::default()).unwrap();
+        assert_eq!(value.to_string(), result);
+    }
+
+    #[test]
+    fn should_fail() {
+        let context = Context::default();
+        let error = decimal("1").div(&decimal("0"), &context).unwrap_err();
+        assert_eq!(error, EvalErrorKind::DivisionByZero);
+        assert_eq!(
+            Number::sqrt(&decimal("-1"), &context),
+            Err(EvalErrorKind::Domain)
+        );
+        assert_eq!(BigDecimal::from_f64(f64::INFINITY), None);
+    }
+}
@@ -0,0 +1,448 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use num::{BigInt, Integer, One, Signed, ToPrimitive, Zero};
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::calc::context::Context;
+use crate::calc::error::EvalErrorKind;
+use crate::calc::function::Function;
+use crate::calc::number::Backend;
+use crate::calc::number::{Number, MAX_EXPONENT};
+
+/// Witnesses of the Miller-Rabin test, which tell primes apart from composite numbers with
+/// certainty below 3.3·10^24, as the first 13 primes.
+const WITNESSES: [u32; 13] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41];
+
+/// Largest divisor tried by trial division before looking for factors with Pollard's rho.
+const TRIAL_DIVISORS: u32 = 1_000;
+
+/// Steps of Pollard's rho before giving up on a factor, which keeps numbers made of two large
+/// primes from hanging the calculator.
+const MAX_STEPS: usize = 1 << 18;
+
+/// Largest number whose factorial is computed exactly, which has 35 660 digits.
+const MAX_FACTORIAL: u64 = 10_000;
+
+impl Number for BigInt {
+    /// Reads integer literals, including exponents like `1e3`, but no fractional digits.
+    fn parse(literal: &str) -> Option<BigInt> {
+        if let Ok(x) = BigInt::from_str(literal) {
+            return Some(x);
+        }
+        let x = BigDecimal::from_str(literal).ok()?;
+        let exponent = x.fractional_digit_count();
+        if x.is_integer() && -exponent <= MAX_EXPONENT {
+            return Some(x.with_scale(0).into_bigint_and_exponent().0);
+        }
+        None
+    }
+
+    fn from_f64(x: f64) -> Option<BigInt> {
+        if x.fract() != 0.0 {
+            return None;
+        }
+        num::FromPrimitive::from_f64(x)
+    }
+
+    fn to_f64(&self) -> f64 {
+        ToPrimitive::to_f64(self).unwrap_or(f64::NAN)
+    }
+
+    fn add(&self, y: &BigInt) -> Result<BigInt, EvalErrorKind> {
+        Ok(self + y)
+    }
+
+    fn sub(&self, y: &BigInt) -> Result<BigInt, EvalErrorKind> {
+        Ok(self - y)
+    }
+
+    fn mul(&self, y: &BigInt) -> Result<BigInt, EvalErrorKind> {
+        Ok(self * y)
+    }
+
+    fn neg(&self) -> Result<BigInt, EvalErrorKind> {
+        Ok(-self)
+    }
+
+    /// Exact when the divisor divides the dividend, inexact otherwise. Programmer mode rounds
+    /// the quotient towards zero instead.
+    fn div(&self, y: &BigInt, context: &Context) -> Result<BigInt, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        if context.backend == Backend::Programmer {
+            return Ok(self / y);
+        }
+        let (quotient, remainder) = self.div_rem(y);
+        match Zero::is_zero(&remainder) {
+            true => Ok(quotient),
+            false => Err(EvalErrorKind::Inexact),
+        }
+    }
+
+    fn pow(&self, y: &BigInt, context: &Context) -> Result<BigInt, EvalErrorKind> {
+        if context.backend == Backend::Programmer {
+            return Ok(match Signed::is_negative(y) {
+                _ if Zero::is_zero(y) => BigInt::one(),
+                true if Zero::is_zero(self) => return Err(EvalErrorKind::DivisionByZero),
+                true if self.abs().is_one() => num::pow::Pow::pow(self, y.magnitude()),
+                true => BigInt::zero(),
+                // Only the low bits are kept anyway, whatever the exponent
+                false => self.modpow(y, &(BigInt::one() << context.word.bits())),
+            });
+        }
+        let n = y.to_i64().filter(|n| n.abs() <= MAX_EXPONENT);
+        match n {
+            None => Err(EvalErrorKind::Overflow),
+            Some(n) if n >= 0 => Ok(num::pow::Pow::pow(self, n as u64)),
+            Some(_) if Zero::is_zero(self) => Err(EvalErrorKind::DivisionByZero),
+            // The only integers whose inverse is an integer
+            Some(n) if self.abs().is_one() => Ok(num::pow::Pow::pow(self, n.unsigned_abs())),
+            Some(_) => Err(EvalErrorKind::Inexact),
+        }
+    }
+
+    /// Exact for perfect squares, inexact otherwise. Programmer mode rounds the root down
+    /// instead.
+    fn sqrt(&self, context: &Context) -> Result<BigInt, EvalErrorKind> {
+        if Signed::is_negative(self) {
+            return Err(EvalErrorKind::Domain);
+        }
+        let root = BigInt::sqrt(self);
+        match &root * &root == *self || context.backend == Backend::Programmer {
+            true => Ok(root),
+            false => Err(EvalErrorKind::Inexact),
+        }
+    }
+
+    fn quotient(&self, y: &BigInt) -> Result<BigInt, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok(self / y)
+    }
+
+    fn remainder(&self, y: &BigInt) -> Result<BigInt, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok(self % y)
+    }
+
+    /// Exact for the functions with integer results, inexact otherwise.
+    fn apply(
+        function: Function,
+        args: &[BigInt],
+        context: &Context,
+    ) -> Result<BigInt, EvalErrorKind> {
+        let word = context.word;
+        let bits = |y: &BigInt| match y.to_u64() {
+            None if Signed::is_negative(y) => Err(EvalErrorKind::Domain),
+            None => Err(EvalErrorKind::Overflow),
+            Some(n) => Ok(n),
+        };
+        let boolean = |b: bool| Ok(BigInt::from(u8::from(b)));
+        match (function, args) {
+            (Function::Abs, [x]) => Ok(x.abs()),
+            (Function::Floor | Function::Ceil | Function::Round | Function::Trunc, [x]) => {
+                Ok(x.clone())
+            }
+            (Function::Sign, [x]) => Ok(x.signum()),
+            (Function::Max, [x, args @ ..]) => Ok(args.iter().fold(x, |m, y| m.max(y)).clone()),
+            (Function::Min, [x, args @ ..]) => Ok(args.iter().fold(x, |m, y| m.min(y)).clone()),
+            (Function::Mod, [_, y]) if Zero::is_zero(y) => Err(EvalErrorKind::DivisionByZero),
+            (Function::Mod, [x, y]) => Ok(x.mod_floor(y)),
+            (Function::Gcd, [x, args @ ..]) => Ok(args.iter().fold(x.clone(), |m, y| m.gcd(y))),
+            (Function::Lcm, [x, args @ ..]) => Ok(args.iter().fold(x.clone(), |m, y| m.lcm(y))),
+            (Function::Modpow | Function::Modinv, [.., m]) if Zero::is_zero(m) => {
+                Err(EvalErrorKind::DivisionByZero)
+            }
+            (Function::Modpow, [x, y, m]) if Signed::is_negative(y) => {
+                let inverse = x.modinv(m).ok_or(EvalErrorKind::Domain)?;
+                Ok(inverse.modpow(&-y, m))
+            }
+            (Function::Modpow, [x, y, m]) => Ok(x.modpow(y, m)),
+            (Function::Modinv, [x, m]) => x.modinv(m).ok_or(EvalErrorKind::Domain),
+            (Function::Isprime, [x]) => boolean(is_prime(x)),
+            (Function::Nextprime, [x]) => Ok(next_prime(x)),
+            (Function::And, [x, y]) => Ok(x & y),
+            (Function::Or, [x, y]) => Ok(x | y),
+            (Function::Xor, [x, y]) => Ok(x ^ y),
+            (Function::Not, [x]) => Ok(!x),
+            (Function::Shl, [x, y]) => match (bits(y)?, context.backend) {
+                // Shifted out of the word anyway
+                (n, Backend::Programmer) => Ok(x << n.min(u64::from(word.bits()))),
+                (n, _) if n > MAX_EXPONENT as u64 => Err(EvalErrorKind::Overflow),
+                (n, _) => Ok(x << n),
+            },
+            (Function::Shr, [x, y]) => match bits(y) {
+                // Shifted out entirely, leaving the sign
+                Err(EvalErrorKind::Overflow) => Ok(-BigInt::from(u8::from(Signed::is_negative(x)))),
+                n => Ok(x >> n?),
+            },
+            (Function::Rol | Function::Ror, [x, y]) => {
+                let size = u64::from(word.bits());
+                let n = y.mod_floor(&BigInt::from(size)).to_u64().unwrap_or(0);
+                let n = if function == Function::Ror {
+                    (size - n) % size
+                } else {
+                    n
+                };
+                let x = word.unsigned(x);
+                Ok(word.wrap(&((&x << n) | (&x >> (size - n)))))
+            }
+            _ => Err(EvalErrorKind::Inexact),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn is_negative(&self) -> bool {
+        Signed::is_negative(self)
+    }
+}
+
+/// Tells primes with the Miller-Rabin test, certain below 3.3·10^24 and with a negligible
+/// chance of error above.
+pub fn is_prime(n: &BigInt) -> bool {
+    if *n < BigInt::from(2) {
+        return false;
+    }
+    for p in WITNESSES.map(BigInt::from) {
+        if *n == p {
+            return true;
+        }
+        if Zero::is_zero(&(n % &p)) {
+            return false;
+        }
+    }
+    let m: BigInt = n - 1;
+    let s = m.trailing_zeros().unwrap_or(0);
+    let d = &m >> s;
+    'witness: for a in WITNESSES.map(BigInt::from) {
+        let mut x = a.modpow(&d, n);
+        if x.is_one() || x == m {
+            continue;
+        }
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == m {
+                continue 'witness;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// The smallest prime greater than a number.
+pub fn next_prime(n: &BigInt) -> BigInt {
+    let mut p = n.max(&BigInt::one()) + 1;
+    while !is_prime(&p) {
+        p += 1;
+    }
+    p
+}
+
+/// The factorial of a natural number, unless too large to compute exactly.
+pub fn factorial(n: &BigInt) -> Option<BigInt> {
+    let n = n.to_u64().filter(|n| *n <= MAX_FACTORIAL)?;
+    Some((2..=n).map(BigInt::from).product())
+}
+
+/// A non-trivial divisor of an odd composite number with Pollard's rho, with Floyd's cycle
+/// detection, if one is found in time.
+fn rho(n: &BigInt) -> Option<BigInt> {
+    for c in 1..=3u32 {
+        let f = |x: &BigInt| (x * x + c) % n;
+        let (mut x, mut y) = (BigInt::from(2), BigInt::from(2));
+        for _ in 0..MAX_STEPS {
+            x = f(&x);
+            y = f(&f(&y));
+            let d = (&x - &y).abs().gcd(n);
+            if d == *n {
+                break;
+            }
+            if !d.is_one() {
+                return Some(d);
+            }
+        }
+    }
+    None
+}
+
+/// The prime factors of a number in increasing order, with repetitions, like `[2, 2, 3]` for
+/// 12. Negative numbers start with -1, and 0 and 1 are their own factors. Fails if a factor
+/// is out of reach.
+pub fn factor(n: &BigInt) -> Option<Vec<BigInt>> {
+    if n.abs() <= BigInt::one() {
+        return Some(vec![n.clone()]);
+    }
+    let mut factors = Vec::new();
+    if Signed::is_negative(n) {
+        factors.push(-BigInt::one());
+    }
+    let mut n = n.abs();
+    for p in (2..=TRIAL_DIVISORS).map(BigInt::from) {
+        if &p * &p > n {
+            break;
+        }
+        while Zero::is_zero(&(&n % &p)) {
+            n /= &p;
+            factors.push(p.clone());
+        }
+    }
+    let mut composite = vec![n];
+    while let Some(n) = composite.pop() {
+        if n.is_one() {
+            continue;
+        }
+        if is_prime(&n) {
+            factors.push(n);
+            continue;
+        }
+        let d = rho(&n)?;
+        composite.push(&n / &d);
+        composite.push(d);
+    }
+    factors.sort();
+    Some(factors)
+}
+
+/// Serializes integers as text, which keeps them exact whatever their size.
+pub fn serialize<S: Serializer>(x: &BigInt, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(x)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigInt, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    BigInt::from_str(&text).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn integer(literal: &str) -> BigInt {
+        literal.parse().unwrap()
+    }
+
+    #[rstest]
+    #[case("12", Some("12"))]
+    #[case("-1e3", Some("-1000"))]
+    #[case("1.0", Some("1"))]
+    #[case("1.5", None)]
+    #[case("1e-3", None)]
+    fn should_parse(#[case] literal: &str, #[case] integer: Option<&str>) {
+        let parsed = BigInt::parse(literal).map(|x| x.to_string());
+        assert_eq!(parsed, integer.map(String::from));
+    }
+
+    #[rstest]
+    #[case("6", "3", Ok("2"))]
+    #[case("7", "2", Err(EvalErrorKind::Inexact))]
+    #[case("7", "0", Err(EvalErrorKind::DivisionByZero))]
+    fn should_divide(
+        #[case] x: &str,
+        #[case] y: &str,
+        #[case] result: Result<&str, EvalErrorKind>,
+    ) {
+        let quotient = integer(x).div(&integer(y), &Context::default());
+        assert_eq!(quotient.map(|x| x.to_string()), result.map(String::from));
+    }
+
+    #[rstest]
+    #[case(
+        "2",
+        "200",
+        Ok("1606938044258990275541962092341162602522202993782792835301376")
+    )]
+    #[case("-1", "-3", Ok("-1"))]
+    #[case("2", "-1", Err(EvalErrorKind::Inexact))]
+    #[case("2", "1000000", Err(EvalErrorKind::Overflow))]
+    fn should_pow(#[case] x: &str, #[case] y: &str, #[case] result: Result<&str, EvalErrorKind>) {
+        let power = Number::pow(&integer(x), &integer(y), &Context::default());
+        assert_eq!(power.map(|x| x.to_string()), result.map(String::from));
+    }
+
+    #[rstest]
+    #[case("-7", "2", "-3", "-1")]
+    #[case("7", "-2", "-3", "1")]
+    fn should_divide_integers(
+        #[case] x: &str,
+        #[case] y: &str,
+        #[case] quotient: &str,
+        #[case] remainder: &str,
+    ) {
+        let (x, y) = (integer(x), integer(y));
+        assert_eq!(x.quotient(&y).unwrap().to_string(), quotient);
+        assert_eq!(x.remainder(&y).unwrap().to_string(), remainder);
+    }
+
+    #[rstest]
+    #[case(Function::Gcd, &["12", "18", "27"], "3")]
+    #[case(Function::Lcm, &["4", "6"], "12")]
+    #[case(Function::Modpow, &["4", "13", "497"], "445")]
+    #[case(Function::Modpow, &["3", "-1", "7"], "5")]
+    #[case(Function::Modinv, &["3", "7"], "5")]
+    #[case(Function::Isprime, &["2147483647"], "1")]
+    #[case(Function::Isprime, &["3215031751"], "0")]
+    #[case(Function::Nextprime, &["89"], "97")]
+    #[case(Function::Nextprime, &["-5"], "2")]
+    #[case(Function::Mod, &["-7", "3"], "2")]
+    fn should_apply(#[case] function: Function, #[case] args: &[&str], #[case] result: &str) {
+        let args: Vec<BigInt> = args.iter().map(|x| integer(x)).collect();
+        let value = BigInt::apply(function, &args, &Context::default()).unwrap();
+        assert_eq!(value.to_string(), result);
+    }
+
+    #[rstest]
+    #[case(Function::And, &["12", "10"], "8")]
+    #[case(Function::Or, &["12", "10"], "14")]
+    #[case(Function::Xor, &["12", "10"], "6")]
+    #[case(Function::Not, &["12"], "-13")]
+    #[case(Function::Shl, &["3", "4"], "48")]
+    #[case(Function::Shr, &["-16", "2"], "-4")]
+    #[case(Function::Shr, &["-16", "100000000000000000000"], "-1")]
+    #[case(Function::Rol, &["1", "-1"], "-9223372036854775808")]
+    #[case(Function::Ror, &["1", "1"], "-9223372036854775808")]
+    #[case(Function::Rol, &["-9223372036854775808", "1"], "1")]
+    fn should_apply_bitwise(
+        #[case] function: Function,
+        #[case] args: &[&str],
+        #[case] result: &str,
+    ) {
+        let args: Vec<BigInt> = args.iter().map(|x| integer(x)).collect();
+        let value = BigInt::apply(function, &args, &Context::default()).unwrap();
+        assert_eq!(value.to_string(), result);
+    }
+
+    #[test]
+    fn should_fail() {
+        let context = Context::default();
+        let args = [integer("2"), integer("4")];
+        let error = BigInt::apply(Function::Modinv, &args, &context);
+        assert_eq!(error, Err(EvalErrorKind::Domain));
+        let args = [integer("2"), integer("0")];
+        let error = BigInt::apply(Function::Modinv, &args, &context);
+        assert_eq!(error, Err(EvalErrorKind::DivisionByZero));
+    }
+
+    #[rstest]
+    #[case("360", &["2", "2", "2", "3", "3", "5"])]
+    #[case("-14", &["-1", "2", "7"])]
+    #[case("1", &["1"])]
+    #[case("1000000007", &["1000000007"])]
+    #[case("600851475143", &["71", "839", "1471", "6857"])]
+    #[case("10403", &["101", "103"])]
+    #[case("1000036000099", &["1000003", "1000033"])]
+    fn should_factor(#[case] n: &str, #[case] factors: &[&str]) {
+        let factors: Vec<BigInt> = factors.iter().map(|x| integer(x)).collect();
+        assert_eq!(factor(&integer(n)), Some(factors));
+    }
+}
@@ -0,0 +1,219 @@
+use std::str::FromStr;
+
+use bigdecimal::BigDecimal;
+use num::pow::Pow;
+use num::{BigInt, BigRational, Signed, ToPrimitive, Zero};
+use serde::{Deserialize, Deserializer, Serializer};
+
+use crate::calc::context::Context;
+use crate::calc::error::EvalErrorKind;
+use crate::calc::function::Function;
+use crate::calc::number::{Number, MAX_EXPONENT};
+
+/// The square root of an integer, if it has an integer one.
+fn exact_sqrt(x: &BigInt) -> Option<BigInt> {
+    let root = x.sqrt();
+    (&root * &root == *x).then_some(root)
+}
+
+impl Number for BigRational {
+    /// Reads decimal literals exactly, so that `0.1` is one tenth.
+    fn parse(literal: &str) -> Option<BigRational> {
+        let (digits, scale) = BigDecimal::from_str(literal)
+            .ok()?
+            .into_bigint_and_exponent();
+        if scale.unsigned_abs() > MAX_EXPONENT.unsigned_abs() {
+            return None;
+        }
+        let power = BigInt::from(10).pow(scale.unsigned_abs());
+        match scale {
+            0.. => Some(BigRational::new(digits, power)),
+            _ => Some(BigRational::from_integer(digits * power)),
+        }
+    }
+
+    fn from_f64(x: f64) -> Option<BigRational> {
+        BigRational::from_float(x)
+    }
+
+    fn to_f64(&self) -> f64 {
+        ToPrimitive::to_f64(self).unwrap_or(f64::NAN)
+    }
+
+    fn add(&self, y: &BigRational) -> Result<BigRational, EvalErrorKind> {
+        Ok(self + y)
+    }
+
+    fn sub(&self, y: &BigRational) -> Result<BigRational, EvalErrorKind> {
+        Ok(self - y)
+    }
+
+    fn mul(&self, y: &BigRational) -> Result<BigRational, EvalErrorKind> {
+        Ok(self * y)
+    }
+
+    fn neg(&self) -> Result<BigRational, EvalErrorKind> {
+        Ok(-self)
+    }
+
+    fn div(&self, y: &BigRational, _context: &Context) -> Result<BigRational, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok(self / y)
+    }
+
+    fn pow(&self, y: &BigRational, _context: &Context) -> Result<BigRational, EvalErrorKind> {
+        if !y.is_integer() {
+            return Err(EvalErrorKind::Inexact);
+        }
+        let n = y.to_i32().filter(|n| i64::from(*n).abs() <= MAX_EXPONENT);
+        match n {
+            None => Err(EvalErrorKind::Overflow),
+            Some(n) if n < 0 && Zero::is_zero(self) => Err(EvalErrorKind::DivisionByZero),
+            Some(n) => Ok(Pow::pow(self, n)),
+        }
+    }
+
+    fn sqrt(&self, _context: &Context) -> Result<BigRational, EvalErrorKind> {
+        if Signed::is_negative(self) {
+            return Err(EvalErrorKind::Domain);
+        }
+        let numer = exact_sqrt(self.numer()).ok_or(EvalErrorKind::Inexact)?;
+        let denom = exact_sqrt(self.denom()).ok_or(EvalErrorKind::Inexact)?;
+        Ok(BigRational::new(numer, denom))
+    }
+
+    fn quotient(&self, y: &BigRational) -> Result<BigRational, EvalErrorKind> {
+        if Zero::is_zero(y) {
+            return Err(EvalErrorKind::DivisionByZero);
+        }
+        Ok((self / y).trunc())
+    }
+
+    fn remainder(&self, y: &BigRational) -> Result<BigRational, EvalErrorKind> {
+        Ok(self - y * self.quotient(y)?)
+    }
+
+    /// Exact for the functions with rational results, inexact otherwise.
+    fn apply(
+        function: Function,
+        args: &[BigRational],
+        _context: &Context,
+    ) -> Result<BigRational, EvalErrorKind> {
+        match (function, args) {
+            (Function::Abs, [x]) => Ok(x.abs()),
+            (Function::Floor, [x]) => Ok(x.floor()),
+            (Function::Ceil, [x]) => Ok(x.ceil()),
+            (Function::Round, [x]) => Ok(x.round()),
+            (Function::Trunc, [x]) => Ok(x.trunc()),
+            (Function::Sign, [x]) => Ok(x.signum()),
+            (Function::Max, [x, args @ ..]) => Ok(args.iter().fold(x, |m, y| m.max(y)).clone()),
+            (Function::Min, [x, args @ ..]) => Ok(args.iter().fold(x, |m, y| m.min(y)).clone()),
+            (Function::Mod, [_, y]) if Zero::is_zero(y) => Err(EvalErrorKind::DivisionByZero),
+            (Function::Mod, [x, y]) => Ok(x - y * (x / y).floor()),
+            _ => Err(EvalErrorKind::Inexact),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn is_negative(&self) -> bool {
+        Signed::is_negative(self)
+    }
+}
+
+/// Writes a fraction as a mixed number, like `3 1/2` for `7/2`.
+pub fn mixed(x: &BigRational) -> String {
+    let (whole, part) = (x.trunc(), x.fract());
+    if Zero::is_zero(&whole) || Zero::is_zero(&part) {
+        return x.to_string();
+    }
+    format!("{} {}", whole, part.abs())
+}
+
+/// Serializes fractions as text, like `"1/3"`.
+pub fn serialize<S: Serializer>(x: &BigRational, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(x)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BigRational, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let (numer, denom) = text.split_once('/').unwrap_or((&text, "1"));
+    let numer = BigInt::from_str(numer).map_err(serde::de::Error::custom)?;
+    let denom = BigInt::from_str(denom).map_err(serde::de::Error::custom)?;
+    if Zero::is_zero(&denom) {
+        return Err(serde::de::Error::custom("Zero denominator"));
+    }
+    Ok(BigRational::new(numer, denom))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+
+    fn rational(literal: &str) -> BigRational {
+        let (numer, denom) = literal.split_once('/').unwrap_or((literal, "1"));
+        BigRational::new(numer.parse().unwrap(), denom.parse().unwrap())
+    }
+
+    #[rstest]
+    #[case("0.1", Some("1/10"))]
+    #[case("1.5e3", Some("1500"))]
+    #[case("2.50", Some("5/2"))]
+    #[case("-.125", Some("-1/8"))]
+    #[case("1e-3", Some("1/1000"))]
+    #[case("1e100001", None)]
+    #[case("1e-100001", None)]
+    fn should_parse(#[case] literal: &str, #[case] fraction: Option<&str>) {
+        let parsed = BigRational::parse(literal).map(|x| x.to_string());
+        assert_eq!(parsed, fraction.map(String::from));
+    }
+
+    #[rstest]
+    #[case("4/9", Ok("2/3"))]
+    #[case("2", Err(EvalErrorKind::Inexact))]
+    #[case("-4", Err(EvalErrorKind::Domain))]
+    fn should_sqrt(#[case] x: &str, #[case] root: Result<&str, EvalErrorKind>) {
+        let result = Number::sqrt(&rational(x), &Context::default());
+        assert_eq!(result.map(|x| x.to_string()), root.map(String::from));
+    }
+
+    #[rstest]
+    #[case("2/3", "2", Ok("4/9"))]
+    #[case("2/3", "-2", Ok("9/4"))]
+    #[case("4", "1/2", Err(EvalErrorKind::Inexact))]
+    #[case("0", "-1", Err(EvalErrorKind::DivisionByZero))]
+    fn should_pow(#[case] x: &str, #[case] y: &str, #[case] power: Result<&str, EvalErrorKind>) {
+        let result = Number::pow(&rational(x), &rational(y), &Context::default());
+        assert_eq!(result.map(|x| x.to_string()), power.map(String::from));
+    }
+
+    #[rstest]
+    #[case(Function::Round, &["7/2"], "4")]
+    #[case(Function::Floor, &["-7/2"], "-4")]
+    #[case(Function::Ceil, &["-7/2"], "-3")]
+    #[case(Function::Trunc, &["-7/2"], "-3")]
+    #[case(Function::Abs, &["-1/3"], "1/3")]
+    #[case(Function::Sign, &["-1/3"], "-1")]
+    #[case(Function::Max, &["1/3", "1/2", "1/4"], "1/2")]
+    #[case(Function::Mod, &["-7/2", "2"], "1/2")]
+    fn should_apply(#[case] function: Function, #[case] args: &[&str], #[case] result: &str) {
+        let args: Vec<BigRational> = args.iter().map(|x| rational(x)).collect();
+        let value = BigRational::apply(function, &args, &Context::default()).unwrap();
+        assert_eq!(value.to_string(), result);
+    }
+
+    #[rstest]
+    #[case("7/2", "3 1/2")]
+    #[case("-7/2", "-3 1/2")]
+    #[case("1/2", "1/2")]
+    #[case("3", "3")]
+    fn should_write_mixed(#[case] x: &str, #[case] text: &str) {
+        assert_eq!(mixed(&rational(x)), text);
+    }
+}
@@ -0,0 +1,783 @@
+use std::ops::RangeInclusive;
+
+use num::BigInt;
+
+use crate::calc::context::{Context, Definition};
+use crate::calc::error::{CalcError, ErrorKind};
+use crate::calc::expr::{Comparison, Expr};
+use crate::calc::function::Function;
+use crate::calc::number::Value;
+use crate::calc::token::{Span, Token};
+
+/// Binding power of a function applied to its operand without parenthesis, like `sqrt 4`.
+const APPLICATION: u8 = 19;
+
+/// Binding power of operators written after their operand, which bind tighter than any other.
+const POSTFIX: u8 = 20;
+
+/// Most operators, functions and parenthesis an expression can nest, so that parsing and
+/// evaluating it cannot overflow the stack.
+const MAX_DEPTH: usize = 256;
+
+/// How an operator is placed relative to its operands, and how it builds its expression.
+pub enum Fixity {
+    /// Before its single operand, like the `-` in `-2`.
+    Prefix(fn(Box<Expr>) -> Expr),
+    /// Between its two operands, like the `-` in `3 - 2`.
+    Infix(fn(Box<Expr>, Box<Expr>) -> Expr),
+    /// After its single operand, like the `!` in `5!`.
+    Postfix(fn(Box<Expr>) -> Expr),
+    /// A named prefix operator which can also be called as `name(operand)`.
+    Function(fn(Box<Expr>) -> Expr),
+}
+
+pub struct Operator {
+    pub token: Token,
+    /// Binding power towards the left and right operands, the higher the tighter the operator
+    /// binds. A left power lower than the right one makes a binary operator left associative.
+    pub power: (u8, u8),
+    pub fixity: Fixity,
+}
+
+/// Rules that reflect conventions about which operations to perform first in order to evaluate
+/// a given mathematical expression.
+/// https://en.wikipedia.org/wiki/Order_of_operations
+///
+/// In postfix notation a token stands for its first entry in the table.
+pub const OPERATORS: &[Operator] = &[
+    // bitwise operations, looser than comparisons as in C
+    Operator {
+        token: Token::Function(Function::Or, None),
+        power: (1, 2),
+        fixity: Fixity::Infix(or),
+    },
+    Operator {
+        token: Token::Function(Function::Xor, None),
+        power: (3, 4),
+        fixity: Fixity::Infix(xor),
+    },
+    Operator {
+        token: Token::Function(Function::And, None),
+        power: (5, 6),
+        fixity: Fixity::Infix(and),
+    },
+    // comparisons, between the bitwise and the shift operations as in C
+    Operator {
+        token: Token::Compare(Comparison::Equal),
+        power: (7, 8),
+        fixity: Fixity::Infix(equal),
+    },
+    Operator {
+        token: Token::Compare(Comparison::NotEqual),
+        power: (7, 8),
+        fixity: Fixity::Infix(not_equal),
+    },
+    Operator {
+        token: Token::Compare(Comparison::Less),
+        power: (7, 8),
+        fixity: Fixity::Infix(less),
+    },
+    Operator {
+        token: Token::Compare(Comparison::LessOrEqual),
+        power: (7, 8),
+        fixity: Fixity::Infix(less_or_equal),
+    },
+    Operator {
+        token: Token::Compare(Comparison::Greater),
+        power: (7, 8),
+        fixity: Fixity::Infix(greater),
+    },
+    Operator {
+        token: Token::Compare(Comparison::GreaterOrEqual),
+        power: (7, 8),
+        fixity: Fixity::Infix(greater_or_equal),
+    },
+    // shifts, looser than arithmetic
+    Operator {
+        token: Token::Function(Function::Shl, None),
+        power: (9, 10),
+        fixity: Fixity::Infix(shl),
+    },
+    Operator {
+        token: Token::Function(Function::Shr, None),
+        power: (9, 10),
+        fixity: Fixity::Infix(shr),
+    },
+    Operator {
+        token: Token::Function(Function::Rol, None),
+        power: (9, 10),
+        fixity: Fixity::Infix(rol),
+    },
+    Operator {
+        token: Token::Function(Function::Ror, None),
+        power: (9, 10),
+        fixity: Fixity::Infix(ror),
+    },
+    // addition and subtraction
+    Operator {
+        token: Token::Plus,
+        power: (11, 12),
+        fixity: Fixity::Infix(Expr::Add),
+    },
+    Operator {
+        token: Token::Minus,
+        power: (11, 12),
+        fixity: Fixity::Infix(Expr::Subtract),
+    },
+    // multiplication and division
+    Operator {
+        token: Token::Star,
+        power: (13, 14),
+        fixity: Fixity::Infix(Expr::Multiply),
+    },
+    Operator {
+        token: Token::Slash,
+        power: (13, 14),
+        fixity: Fixity::Infix(Expr::Divide),
+    },
+    Operator {
+        token: Token::SlashSlash,
+        power: (13, 14),
+        fixity: Fixity::Infix(Expr::Quotient),
+    },
+    Operator {
+        token: Token::Rem,
+        power: (13, 14),
+        fixity: Fixity::Infix(Expr::Remainder),
+    },
+    // signs bind tighter than products but looser than powers, so -2^2 is -4
+    Operator {
+        token: Token::Minus,
+        power: (0, 15),
+        fixity: Fixity::Prefix(Expr::Negate),
+    },
+    Operator {
+        token: Token::Plus,
+        power: (0, 15),
+        fixity: Fixity::Prefix(identity),
+    },
+    Operator {
+        token: Token::Negate,
+        power: (0, 15),
+        fixity: Fixity::Function(Expr::Negate),
+    },
+    // exponentiation, which is right associative
+    Operator {
+        token: Token::Caret,
+        power: (18, 17),
+        fixity: Fixity::Infix(Expr::Power),
+    },
+    Operator {
+        token: Token::Sqrt,
+        power: (0, APPLICATION),
+        fixity: Fixity::Function(Expr::Sqrt),
+    },
+    // factorials, percents and superscript powers, so that sqrt 4! is the root of 24
+    Operator {
+        token: Token::Bang,
+        power: (POSTFIX, 0),
+        fixity: Fixity::Postfix(Expr::Factorial),
+    },
+    Operator {
+        token: Token::Percent,
+        power: (POSTFIX, 0),
+        fixity: Fixity::Postfix(Expr::Percent),
+    },
+    Operator {
+        token: Token::Squared,
+        power: (POSTFIX, 0),
+        fixity: Fixity::Postfix(squared),
+    },
+    Operator {
+        token: Token::Cubed,
+        power: (POSTFIX, 0),
+        fixity: Fixity::Postfix(cubed),
+    },
+];
+
+// Boxed to fit in the table next to the `Expr` constructors
+#[allow(clippy::boxed_local)]
+fn identity(x: Box<Expr>) -> Expr {
+    *x
+}
+
+/// Defines the constructors of powers to a fixed integer exponent, which numbers of any
+/// backend are raised to exactly.
+macro_rules! power {
+    ($($name:ident => $exponent:literal),*) => {
+        $(
+            fn $name(x: Box<Expr>) -> Expr {
+                let exponent = Value::Integer(BigInt::from($exponent));
+                Expr::Power(x, Box::from(Expr::Number(exponent)))
+            }
+        )*
+    };
+}
+
+power!(squared => 2, cubed => 3);
+
+/// Defines the constructors of calls to binary functions which are also infix operators.
+macro_rules! binary {
+    ($($name:ident => $function:ident),*) => {
+        $(
+            #[allow(clippy::boxed_local)]
+            fn $name(x: Box<Expr>, y: Box<Expr>) -> Expr {
+                Expr::Function(Function::$function, vec![*x, *y])
+            }
+        )*
+    };
+}
+
+binary!(and => And, or => Or, xor => Xor, shl => Shl, shr => Shr, rol => Rol, ror => Ror);
+
+/// Defines the constructors of comparisons.
+macro_rules! compare {
+    ($($name:ident => $comparison:ident),*) => {
+        $(
+            fn $name(x: Box<Expr>, y: Box<Expr>) -> Expr {
+                Expr::Compare(Comparison::$comparison, x, y)
+            }
+        )*
+    };
+}
+
+compare!(
+    equal => Equal,
+    not_equal => NotEqual,
+    less => Less,
+    less_or_equal => LessOrEqual,
+    greater => Greater,
+    greater_or_equal => GreaterOrEqual
+);
+
+impl Operator {
+    /// The operator a token stands for in postfix notation.
+    pub fn find(token: &Token) -> Option<&'static Operator> {
+        OPERATORS.iter().find(|op| op.token == *token)
+    }
+
+    fn prefix(token: &Token) -> Option<&'static Operator> {
+        OPERATORS.iter().find(|op| {
+            op.token == *token && matches!(op.fixity, Fixity::Prefix(_) | Fixity::Function(_))
+        })
+    }
+
+    fn infix(token: &Token) -> Option<&'static Operator> {
+        OPERATORS.iter().find(|op| {
+            op.token == *token && matches!(op.fixity, Fixity::Infix(_) | Fixity::Postfix(_))
+        })
+    }
+}
+
+/// A line of infix input.
+#[derive(Debug)]
+pub enum Statement {
+    Expr(Expr),
+    /// Stores the value of an expression in a variable, like `x = 3 * 4`.
+    Assign(String, Expr),
+    /// Defines a function, like `def f(x, y) = x^2 + y`.
+    Define(String, Definition),
+    /// A stack command given on its own, like `undo`.
+    Command(Token, Span),
+}
+
+/// A Pratt parser building expressions out of infix input.
+/// https://en.wikipedia.org/wiki/Operator-precedence_parser#Pratt_parsing
+pub struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<(Token, Span)>,
+    position: usize,
+    /// Tells which variables and functions are defined.
+    context: &'a Context<'a>,
+    /// The function being defined along with its parameters, which its body may refer to.
+    definition: Option<(String, Vec<String>)>,
+    /// How many expressions the one being parsed is nested in.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Parses a line of infix input, which is `None` when there is nothing but whitespace.
+    pub fn parse(input: &'a str, context: &'a Context) -> Result<Option<Statement>, CalcError> {
+        let mut parser = Parser {
+            input,
+            tokens: Token::scan(input),
+            position: 0,
+            context,
+            definition: None,
+            depth: 0,
+        };
+        let statement = match parser.tokens.as_slice() {
+            [] => return Ok(None),
+            [(token, span)] if token.is_command() => {
+                return Ok(Some(Statement::Command(token.clone(), *span)));
+            }
+            [(Token::Identifier(name), _), (Token::Assign, _), ..] => {
+                let name = name.clone();
+                parser.position = 2;
+                Statement::Assign(name, parser.expression(0)?)
+            }
+            [(Token::Define, _), ..] => {
+                parser.position = 1;
+                parser.define()?
+            }
+            _ => Statement::Expr(parser.expression(0)?),
+        };
+        match parser.next() {
+            Some((token, span)) => Err(parser.unexpected(&token, span)),
+            None => Ok(Some(statement)),
+        }
+    }
+
+    fn expression(&mut self, power: u8) -> Result<Expr, CalcError> {
+        if self.depth == MAX_DEPTH {
+            let span = self.peek().map_or(self.end(), |(_, span)| *span);
+            return Err(self.error(ErrorKind::TooDeep, span));
+        }
+        self.depth += 1;
+        let expr = self.operation(power);
+        self.depth -= 1;
+        expr
+    }
+
+    /// An operand followed by the operators binding at least as tight as the given power.
+    fn operation(&mut self, power: u8) -> Result<Expr, CalcError> {
+        let mut lhs = self.operand()?;
+        while let Some((token, _)) = self.peek() {
+            let Some(operator) = Operator::infix(token) else {
+                break;
+            };
+            if operator.power.0 < power {
+                break;
+            }
+            self.next();
+            lhs = match operator.fixity {
+                Fixity::Infix(expr) => {
+                    let rhs = self.expression(operator.power.1)?;
+                    expr(Box::from(lhs), Box::from(rhs))
+                }
+                Fixity::Postfix(expr) => expr(Box::from(lhs)),
+                Fixity::Prefix(_) | Fixity::Function(_) => unreachable!(),
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn operand(&mut self) -> Result<Expr, CalcError> {
+        let Some((token, span)) = self.next() else {
+            return Err(self.error(ErrorKind::MissingOperand, self.end()));
+        };
+        match token {
+            Token::Number(literal) => match self.context.backend.parse(&literal) {
+                Some(value) => Ok(Expr::Number(self.context.wrap(value))),
+                None => Err(self.error(ErrorKind::InvalidNumber, span)),
+            },
+            Token::Constant(c) => Ok(Expr::Constant(c)),
+            Token::Identifier(name) => self.identifier(name, span),
+            Token::GroupOpen => self.group(span),
+            Token::GroupClose => Err(self.error(ErrorKind::MissingOperand, span)),
+            Token::Function(function, None) => {
+                let args = self.call(function.arity(), span)?;
+                Ok(Expr::Function(function, args))
+            }
+            token => match Operator::prefix(&token) {
+                Some(operator) => self.prefix(operator),
+                None => Err(self.unexpected(&token, span)),
+            },
+        }
+    }
+
+    fn prefix(&mut self, operator: &Operator) -> Result<Expr, CalcError> {
+        match operator.fixity {
+            // Called with its operand in parenthesis, so that sqrt(4)^2 is 4
+            Fixity::Function(expr) if matches!(self.peek(), Some((Token::GroupOpen, _))) => {
+                let (_, open) = self.next().unwrap();
+                Ok(expr(Box::from(self.group(open)?)))
+            }
+            Fixity::Function(expr) | Fixity::Prefix(expr) => {
+                let x = self.expression(operator.power.1)?;
+                Ok(expr(Box::from(x)))
+            }
+            Fixity::Infix(_) | Fixity::Postfix(_) => unreachable!(),
+        }
+    }
+
+    /// A parameter of the function being defined, a call to a user-defined function, a
+    /// variable, or a named number like `i`, in that order.
+    fn identifier(&mut self, name: String, span: Span) -> Result<Expr, CalcError> {
+        if let Some((_, params)) = &self.definition {
+            if params.contains(&name) {
+                return Ok(Expr::Variable(name));
+            }
+        }
+        let arity = match &self.definition {
+            Some((defined, params)) if *defined == name => Some(params.len()),
+            _ => self.context.definitions.get(&name).map(|d| d.params.len()),
+        };
+        if let Some(arity) = arity {
+            let args = self.call(arity..=arity, span)?;
+            return Ok(Expr::Call(name, args));
+        }
+        if self.context.variables.contains_key(&name) {
+            return Ok(Expr::Variable(name));
+        }
+        match Value::named(&name) {
+            Some(value) => Ok(Expr::Number(value)),
+            None => Err(self.error(ErrorKind::UndefinedVariable, span)),
+        }
+    }
+
+    /// Parses the arguments of `name(x, y, ...)`, or the single operand of `name x`.
+    fn call(&mut self, arity: RangeInclusive<usize>, name: Span) -> Result<Vec<Expr>, CalcError> {
+        let args = match self.peek() {
+            Some((Token::GroupOpen, _)) => {
+                let (_, open) = self.next().unwrap();
+                self.arguments(open)?
+            }
+            // Functions without parameters need no parenthesis either
+            _ if *arity.end() == 0 => vec![],
+            _ => vec![self.expression(APPLICATION)?],
+        };
+        if !arity.contains(&args.len()) {
+            let span = Span::new(name.start, self.tokens[self.position - 1].1.end);
+            return Err(self.error(ErrorKind::Arity, span));
+        }
+        Ok(args)
+    }
+
+    /// Parses `name(x, y, ...) = body` following `def`.
+    fn define(&mut self) -> Result<Statement, CalcError> {
+        let name = self.name()?;
+        let open = match self.next() {
+            Some((Token::GroupOpen, open)) => open,
+            Some((token, span)) => return Err(self.unexpected(&token, span)),
+            None => return Err(self.error(ErrorKind::MissingOperand, self.end())),
+        };
+        let mut params: Vec<String> = Vec::new();
+        if let Some((Token::GroupClose, _)) = self.peek() {
+            self.next();
+        } else {
+            loop {
+                let span = self.peek().map(|(_, span)| *span);
+                let param = self.name()?;
+                if params.contains(&param) {
+                    let span = span.unwrap_or(self.end());
+                    return Err(self.error(ErrorKind::DuplicateParameter, span));
+                }
+                params.push(param);
+                match self.next() {
+                    Some((Token::Comma, _)) => continue,
+                    Some((Token::GroupClose, _)) => break,
+                    Some((token, span)) => return Err(self.unexpected(&token, span)),
+                    None => return Err(self.error(ErrorKind::UnclosedGroup, open)),
+                }
+            }
+        }
+        match self.next() {
+            Some((Token::Assign, _)) => {}
+            Some((token, span)) => return Err(self.unexpected(&token, span)),
+            None => return Err(self.error(ErrorKind::MissingOperand, self.end())),
+        }
+        self.definition = Some((name.clone(), params.clone()));
+        let body = self.expression(0)?;
+        Ok(Statement::Define(name, Definition { params, body }))
+    }
+
+    fn name(&mut self) -> Result<String, CalcError> {
+        match self.next() {
+            Some((Token::Identifier(name), _)) => Ok(name),
+            Some((_, span)) => Err(self.error(ErrorKind::ExpectedName, span)),
+            None => Err(self.error(ErrorKind::ExpectedName, self.end())),
+        }
+    }
+
+    fn arguments(&mut self, open: Span) -> Result<Vec<Expr>, CalcError> {
+        let mut args = Vec::new();
+        if let Some((Token::GroupClose, _)) = self.peek() {
+            self.next();
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression(0)?);
+            match self.next() {
+                Some((Token::Comma, _)) => continue,
+                Some((Token::GroupClose, _)) => return Ok(args),
+                Some((token, span)) => return Err(self.unexpected(&token, span)),
+                None => return Err(self.error(ErrorKind::UnclosedGroup, open)),
+            }
+        }
+    }
+
+    fn group(&mut self, open: Span) -> Result<Expr, CalcError> {
+        if let Some((Token::GroupClose, close)) = self.peek() {
+            let span = Span::new(open.start, close.end);
+            return Err(self.error(ErrorKind::EmptyGroup, span));
+        }
+        let expr = self.expression(0)?;
+        match self.next() {
+            Some((Token::GroupClose, _)) => Ok(expr),
+            Some((token, span)) => Err(self.unexpected(&token, span)),
+            None => Err(self.error(ErrorKind::UnclosedGroup, open)),
+        }
+    }
+
+    fn peek(&self) -> Option<&(Token, Span)> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<(Token, Span)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// The empty span at the end of the input.
+    fn end(&self) -> Span {
+        Span::new(self.input.len(), self.input.len())
+    }
+
+    fn error(&self, kind: ErrorKind, span: Span) -> CalcError {
+        CalcError::new(kind, self.input, span)
+    }
+
+    /// The error for a token found where it cannot be used.
+    fn unexpected(&self, token: &Token, span: Span) -> CalcError {
+        let kind = match token {
+            Token::Unknown(_) => ErrorKind::UnknownToken,
+            Token::GroupClose => ErrorKind::UnopenedGroup,
+            Token::Number(_)
+            | Token::Constant(_)
+            | Token::Identifier(_)
+            | Token::GroupOpen
+            | Token::Function(..) => ErrorKind::UnexpectedOperand,
+            _ if Operator::prefix(token).is_some() => ErrorKind::UnexpectedOperand,
+            _ => ErrorKind::UnexpectedToken,
+        };
+        self.error(kind, span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::*;
+
+    use super::*;
+    use crate::calc::context::{Definitions, Variables};
+    use crate::calc::number::Value;
+
+    fn parse(infix: &str) -> Result<Expr, CalcError> {
+        let variables = Variables::from([(String::from("x"), Expr::Number(Value::Float(2.0)))]);
+        let definitions = Definitions::from([(
+            String::from("sq"),
+            Definition {
+                params: vec![String::from("x")],
+                body: Expr::Number(Value::Float(0.0)),
+            },
+        )]);
+        let context = Context {
+            variables: &variables,
+            definitions: &definitions,
+            ..Context::default()
+        };
+        match Parser::parse(infix, &context)? {
+            Some(Statement::Expr(expr)) => Ok(expr),
+            other => panic!("Not an expression: {:?}", other),
+        }
+    }
+
+    #[rstest]
+    #[case("2", "2")]
+    #[case("2 + 2", "2 2 +")]
+    #[case("2 - 2", "2 2 -")]
+    #[case("2 * 2", "2 2 *")]
+    #[case("2 / 2", "2 2 /")]
+    #[case("2 ^ 2", "2 2 ^")]
+    #[case("2 * (3 + 5)", "2 3 5 + *")]
+    #[case("sqrt 4", "4 sqrt")]
+    #[case("3 + 4 * 2 / ( 1 - 5 ) ^ 2 ^ 3", "3 4 2 * 1 5 - 2 3 ^ ^ / +")]
+    // No spaces syntax
+    #[case("2+2", "2 2 +")]
+    #[case("2-2", "2 2 -")]
+    #[case("2*2", "2 2 *")]
+    #[case("2/2", "2 2 /")]
+    #[case("2^2", "2 2 ^")]
+    #[case("3+4 * 2 / (1-5)^2^3", "3 4 2 * 1 5 - 2 3 ^ ^ / +")]
+    // Associativity
+    #[case("1 - 2 - 3", "1 2 - 3 -")]
+    #[case("8 / 4 / 2", "8 4 / 2 /")]
+    #[case("2 ^ 3 * 4", "2 3 ^ 4 *")]
+    // Decimal literals
+    #[case("1.5 + .5", "1.5 0.5 +")]
+    #[case("2e-3*1_000", "0.002 1000 *")]
+    #[case("1.5E2/3.", "150 3 /")]
+    // Signs
+    #[case("-3 + 5", "3 neg 5 +")]
+    #[case("2 * -4", "2 4 neg *")]
+    #[case("-2^2", "2 2 ^ neg")]
+    #[case("2^-2", "2 2 neg ^")]
+    #[case("-(1 - -2)", "1 2 neg - neg")]
+    #[case("+3 - +2", "3 2 -")]
+    #[case("--3", "3 neg neg")]
+    // Function calls
+    #[case("sqrt(4)", "4 sqrt")]
+    #[case("sqrt(2 + 2) * 3", "2 2 + sqrt 3 *")]
+    #[case("neg(2)^2", "2 neg 2 ^")]
+    #[case("neg 2^2", "2 2 ^ neg")]
+    #[case("max(1, 2, 3)", "1 2 3 max:3")]
+    #[case("max(1, 2) + 1", "1 2 max 1 +")]
+    #[case("log(8, 2)", "8 2 log:2")]
+    #[case("log 100 * 2", "100 log 2 *")]
+    #[case("hypot(3, 4)^2", "3 4 hypot 2 ^")]
+    #[case("min(1 + 2, max(3, 4), -5)", "1 2 + 3 4 max 5 neg min:3")]
+    // Constants
+    #[case("2 * pi", "2 pi *")]
+    #[case("2*π", "2 pi *")]
+    #[case("τ/φ - e^2", "tau phi / e 2 ^ -")]
+    #[case("-inf + nan", "inf neg nan +")]
+    // Variables
+    #[case("x ^ 2", "x 2 ^")]
+    #[case("max(x, 1)", "x 1 max")]
+    // User-defined functions
+    #[case("sq(3) + sq 2", "3 sq 2 sq +")]
+    #[case("sq(x)^2", "x sq 2 ^")]
+    // Integer and bitwise operations
+    #[case("7 // 2 rem 3", "7 2 // 3 rem")]
+    #[case("1 or 2 xor 3 and 4", "1 2 3 4 and xor or")]
+    #[case("1 shl 2 + 3", "1 2 3 + shl")]
+    #[case("not 1 and and(2, 3)", "1 not 2 3 and and")]
+    #[case("-1 rol 2^3", "1 neg 2 3 ^ rol")]
+    // Postfix operators
+    #[case("5!", "5 !")]
+    #[case("-3!^2", "3 ! 2 ^ neg")]
+    #[case("2^3!", "2 3 ! ^")]
+    #[case("sqrt 4!", "4 ! sqrt")]
+    #[case("x²+x³", "x 2 ^ x 3 ^ +")]
+    #[case("(1+2)²!", "1 2 + 2 ^ !")]
+    #[case("200 + 10%", "200 10 % +")]
+    #[case("50% * 8", "50 % 8 *")]
+    // Comparisons
+    #[case("1 + 2 == 3", "1 2 + 3 ==")]
+    #[case("x² >= 2 or 1", "x 2 ^ 2 >= 1 or")]
+    #[case("1 < 2 and 3 < 4", "1 2 < 3 4 < and")]
+    #[case("4 == 1 shl 2", "4 1 2 shl ==")]
+    #[case("5!=120", "5 120 !=")]
+    #[case("1 < 2 != false", "1 2 < false !=")]
+    fn should_parse(#[case] infix: &str, #[case] postfix: &str) {
+        let expr = parse(infix).unwrap();
+        assert_eq!(expr.to_string(), postfix);
+    }
+
+    #[rstest]
+    #[case("(2 + 3", ErrorKind::UnclosedGroup, Span::new(0, 1))]
+    #[case("((2 + 3)", ErrorKind::UnclosedGroup, Span::new(0, 1))]
+    #[case("2 + 3)", ErrorKind::UnopenedGroup, Span::new(5, 6))]
+    #[case("(2) + 3)", ErrorKind::UnopenedGroup, Span::new(7, 8))]
+    #[case("()", ErrorKind::EmptyGroup, Span::new(0, 2))]
+    #[case("2 * ( ) + 1", ErrorKind::EmptyGroup, Span::new(4, 7))]
+    #[case("2 3", ErrorKind::UnexpectedOperand, Span::new(2, 3))]
+    #[case("(1 + 2) 3", ErrorKind::UnexpectedOperand, Span::new(8, 9))]
+    #[case("2 (3)", ErrorKind::UnexpectedOperand, Span::new(2, 3))]
+    #[case("2 sqrt 4", ErrorKind::UnexpectedOperand, Span::new(2, 6))]
+    #[case("2 +", ErrorKind::MissingOperand, Span::new(3, 3))]
+    #[case("(2 +)", ErrorKind::MissingOperand, Span::new(4, 5))]
+    #[case("2 + * 3", ErrorKind::UnexpectedToken, Span::new(4, 5))]
+    #[case("2 + undo", ErrorKind::UnexpectedToken, Span::new(4, 8))]
+    #[case("2 + $", ErrorKind::UnknownToken, Span::new(4, 5))]
+    #[case("!2", ErrorKind::UnexpectedToken, Span::new(0, 1))]
+    #[case("2 * %", ErrorKind::UnexpectedToken, Span::new(4, 5))]
+    #[case("1, 2", ErrorKind::UnexpectedToken, Span::new(1, 2))]
+    #[case("(1, 2)", ErrorKind::UnexpectedToken, Span::new(2, 3))]
+    #[case("hypot(3)", ErrorKind::Arity, Span::new(0, 8))]
+    #[case("1 + log(1, 2, 3)", ErrorKind::Arity, Span::new(4, 16))]
+    #[case("max 2", ErrorKind::Arity, Span::new(0, 5))]
+    #[case("max()", ErrorKind::Arity, Span::new(0, 5))]
+    #[case("max(1, 2", ErrorKind::UnclosedGroup, Span::new(3, 4))]
+    #[case("max(1,)", ErrorKind::MissingOperand, Span::new(6, 7))]
+    #[case("2 pi", ErrorKind::UnexpectedOperand, Span::new(2, 4))]
+    #[case("2 + y", ErrorKind::UndefinedVariable, Span::new(4, 5))]
+    #[case("x x", ErrorKind::UnexpectedOperand, Span::new(2, 3))]
+    #[case("2 = 3", ErrorKind::UnexpectedToken, Span::new(2, 3))]
+    #[case("1 + x = 3", ErrorKind::UnexpectedToken, Span::new(6, 7))]
+    #[case("sq(1, 2)", ErrorKind::Arity, Span::new(0, 8))]
+    #[case("sq()", ErrorKind::Arity, Span::new(0, 4))]
+    #[case("2 * 1e400", ErrorKind::InvalidNumber, Span::new(4, 9))]
+    fn should_reject(#[case] infix: &str, #[case] kind: ErrorKind, #[case] span: Span) {
+        let error = parse(infix).unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.span, span);
+    }
+
+    #[test]
+    fn should_limit_depth() {
+        let infix = format!("{}1{}", "(".repeat(200), ")".repeat(200));
+        assert_eq!(parse(&infix).unwrap().to_string(), "1");
+        for infix in [
+            format!("{}1{}", "(".repeat(50_000), ")".repeat(50_000)),
+            format!("{}1", "-".repeat(50_000)),
+            format!("2{}", "^2".repeat(50_000)),
+        ] {
+            let error = parse(&infix).unwrap_err();
+            assert_eq!(error.kind, ErrorKind::TooDeep);
+        }
+    }
+
+    #[test]
+    fn should_parse_commands() {
+        let context = Context::default();
+        let statement = Parser::parse(" undo ", &context).unwrap();
+        assert!(matches!(
+            statement,
+            Some(Statement::Command(Token::Undo, Span { start: 1, end: 5 }))
+        ));
+        assert!(Parser::parse("  ", &context).unwrap().is_none());
+    }
+
+    #[test]
+    fn should_parse_assignment() {
+        let context = Context::default();
+        let statement = Parser::parse("y = 3 * 4", &context).unwrap();
+        let Some(Statement::Assign(name, expr)) = statement else {
+            panic!("Not an assignment: {:?}", statement);
+        };
+        assert_eq!(name, "y");
+        assert_eq!(expr.to_string(), "3 4 *");
+
+        let error = Parser::parse("y = ", &context).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::MissingOperand);
+    }
+
+    #[rstest]
+    #[case("def f(x, y) = x^2 + y", "f", &["x", "y"], "x 2 ^ y +")]
+    #[case("def one() = 1", "one", &[], "1")]
+    #[case("def f(n) = n * f(n - 1)", "f", &["n"], "n n 1 - f *")]
+    fn should_parse_definition(
+        #[case] infix: &str,
+        #[case] name: &str,
+        #[case] params: &[&str],
+        #[case] body: &str,
+    ) {
+        let context = Context::default();
+        let statement = Parser::parse(infix, &context).unwrap();
+        let Some(Statement::Define(defined, definition)) = statement else {
+            panic!("Not a definition: {:?}", statement);
+        };
+        assert_eq!(defined, name);
+        assert_eq!(definition.params, params);
+        assert_eq!(definition.body.to_string(), body);
+    }
+
+    #[rstest]
+    #[case("def", ErrorKind::ExpectedName, Span::new(3, 3))]
+    #[case("def sin(x) = x", ErrorKind::ExpectedName, Span::new(4, 7))]
+    #[case("def f = 1", ErrorKind::UnexpectedToken, Span::new(6, 7))]
+    #[case("def f(x, 2) = x", ErrorKind::ExpectedName, Span::new(9, 10))]
+    #[case("def f(x, x) = x", ErrorKind::DuplicateParameter, Span::new(9, 10))]
+    #[case("def f(x", ErrorKind::UnclosedGroup, Span::new(5, 6))]
+    #[case("def f(x) x", ErrorKind::UnexpectedOperand, Span::new(9, 10))]
+    #[case("def f(x) = y", ErrorKind::UndefinedVariable, Span::new(11, 12))]
+    #[case("def f(x) = f(x, x)", ErrorKind::Arity, Span::new(11, 18))]
+    fn should_reject_definition(#[case] infix: &str, #[case] kind: ErrorKind, #[case] span: Span) {
+        let error = Parser::parse(infix, &Context::default()).unwrap_err();
+        assert_eq!(error.kind, kind);
+        assert_eq!(error.span, span);
+    }
+}
@@ -1,21 +1,129 @@
 use regex::Regex;
-use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::sync::OnceLock;
 
-#[derive(Debug, PartialEq)]
+use crate::calc::constant::Constant;
+use crate::calc::context::{Angle, Coordinates, Radix};
+use crate::calc::expr::Comparison;
+use crate::calc::function::Function;
+
+/// Numeric literals shared by both notations: decimals, leading-dot numbers, exponents and `_`
+/// as a digit separator, e.g. `1_000.5`, `.5` or `2e-3`.
+const NUMBER: &str = r"(?:[0-9](?:_?[0-9])*(?:\.(?:[0-9](?:_?[0-9])*)?)?|\.[0-9](?:_?[0-9])*)(?:[eE][+-]?[0-9](?:_?[0-9])*)?";
+
+/// Integer literals in base 16, 8 or 2, e.g. `0xFF`, `0o17` or `0b1010_1010`.
+const RADIX: &str =
+    r"0[xX][0-9a-fA-F](?:_?[0-9a-fA-F])*|0[oO][0-7](?:_?[0-7])*|0[bB][01](?:_?[01])*";
+
+/// Byte range of a token in the input it was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Span::new(range.start, range.end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
-    Number(f64),
+    /// A numeric literal without its digit separators, read by the backend of the calculator.
+    Number(String),
+    Constant(Constant),
     Plus,
     Minus,
     Slash,
+    /// Integer division, rounding the quotient towards zero.
+    SlashSlash,
+    /// Remainder of the integer division.
+    Rem,
     Star,
     Sqrt,
     Caret,
+    /// Factorial, written after its operand.
+    Bang,
+    /// Hundredth of its operand, written after it.
+    Percent,
+    /// Square and cube, written after their operand as superscripts.
+    Squared,
+    Cubed,
+    Compare(Comparison),
+    Negate,
+    /// A named function, with the number of arguments to take from the stack in postfix
+    /// notation when given as `name:count`.
+    Function(Function, Option<usize>),
     GroupOpen,
     GroupClose,
+    Comma,
+    /// A word which is none of the known ones, naming a variable.
+    Identifier(String),
+    /// A quoted variable name, like `'x`, which is not looked up.
+    Name(String),
+    Assign,
+    /// Starts the definition of a function, like `def f(x) = x^2`.
+    Define,
+    /// Stores a value in the named or numbered register on top of the stack, as `5 'x sto`
+    /// or `5 1 sto`.
+    Store,
+    Recall,
+    // Memory keys, working on register 0
+    MemoryAdd,
+    MemorySubtract,
+    MemoryClear,
+    MemoryRecall,
+    Angle(Angle),
+    Coordinates(Coordinates),
+    Radix(Radix),
     Undo,
+    Redo,
+    /// Starts recording a macro named by the next token, whose tokens are kept rather than
+    /// applied until `end`.
+    Record,
+    End,
+    // Control structures of postfix programs, as `x 0 < if then x neg else x end`,
+    // `1 10 for i i next` and `while x 0 > repeat ... end`
+    If,
+    Then,
+    Else,
+    For,
+    Next,
+    While,
+    Repeat,
+    /// Quotes the tokens up to the matching `>>` as a program, which `eval` runs.
+    ProgramOpen,
+    ProgramClose,
+    Eval,
     Pop,
     Clear,
+    // Stack commands, the ones with a count taking it from the top of the stack
+    Swap,
+    Dup,
+    /// Copies the second entry of the stack on top.
+    Over,
+    /// Moves the third entry of the stack on top.
+    Rot,
+    /// Moves the entry at the given level on top, the top being level 1.
+    Roll,
+    /// Copies the entry at the given level on top.
+    Pick,
+    DropN,
+    DupN,
+    /// Pushes the number of entries of the stack.
+    Depth,
+    Reverse,
+    /// Replaces the integer on top of the stack by its prime factors.
+    Factor,
     Unknown(String),
 }
 
@@ -26,87 +134,177 @@ impl Token {
             "+" => Token::Plus,
             "-" => Token::Minus,
             "/" | "÷" => Token::Slash,
+            "//" => Token::SlashSlash,
+            "rem" => Token::Rem,
             "*" | "×" => Token::Star,
             "^" => Token::Caret,
+            "!" => Token::Bang,
+            "%" => Token::Percent,
+            "²" => Token::Squared,
+            "³" => Token::Cubed,
+            "==" => Token::Compare(Comparison::Equal),
+            "!=" | "≠" => Token::Compare(Comparison::NotEqual),
+            "<" => Token::Compare(Comparison::Less),
+            "<=" | "≤" => Token::Compare(Comparison::LessOrEqual),
+            ">" => Token::Compare(Comparison::Greater),
+            ">=" | "≥" => Token::Compare(Comparison::GreaterOrEqual),
             // Functions
             "sqrt" => Token::Sqrt,
+            "neg" | "chs" => Token::Negate,
             "undo" => Token::Undo,
-            "rm" => Token::Pop,
+            "redo" => Token::Redo,
+            "rm" | "drop" => Token::Pop,
             "clear" => Token::Clear,
+            "swap" => Token::Swap,
+            "dup" => Token::Dup,
+            "over" => Token::Over,
+            "rot" => Token::Rot,
+            "roll" => Token::Roll,
+            "pick" => Token::Pick,
+            "dropn" => Token::DropN,
+            "dupn" => Token::DupN,
+            "depth" => Token::Depth,
+            "reverse" => Token::Reverse,
+            "factor" => Token::Factor,
+            // Macros
+            "rec" => Token::Record,
+            "end" => Token::End,
+            // Programs
+            "if" => Token::If,
+            "then" => Token::Then,
+            "else" => Token::Else,
+            "for" => Token::For,
+            "next" => Token::Next,
+            "while" => Token::While,
+            "repeat" => Token::Repeat,
+            "<<" | "«" => Token::ProgramOpen,
+            ">>" | "»" => Token::ProgramClose,
+            "eval" => Token::Eval,
+            // Variables
+            "=" => Token::Assign,
+            "def" => Token::Define,
+            "sto" => Token::Store,
+            "rcl" => Token::Recall,
+            "m+" => Token::MemoryAdd,
+            "m-" => Token::MemorySubtract,
+            "mc" => Token::MemoryClear,
+            "mr" => Token::MemoryRecall,
+            // Modes
+            "deg" => Token::Angle(Angle::Degrees),
+            "rad" => Token::Angle(Angle::Radians),
+            "grad" => Token::Angle(Angle::Gradians),
+            "rect" => Token::Coordinates(Coordinates::Rectangular),
+            "polar" => Token::Coordinates(Coordinates::Polar),
+            "hex" => Token::Radix(Radix::Hex),
+            "dec" => Token::Radix(Radix::Dec),
+            "oct" => Token::Radix(Radix::Oct),
+            "bin" => Token::Radix(Radix::Bin),
             // Grouping
             "(" => Token::GroupOpen,
             ")" => Token::GroupClose,
-            other => other
-                .parse::<f64>()
+            "," => Token::Comma,
+            other => Token::number(other)
                 .map(Token::Number)
+                .or_else(|| Constant::new(other).map(Token::Constant))
+                .or_else(|| Token::function(other))
+                .or_else(|| Token::identifier(other))
                 .unwrap_or(Token::Unknown(String::from(other))),
         }
     }
 
-    pub fn shunting_yard(input: &str) -> Vec<Token> {
-        let mut operators: VecDeque<Token> = VecDeque::new();
-        let mut output = Vec::new();
-        let tokens = Regex::new(r"[0-9]+|[a-zA-Z]+|[^\s\t\n]").expect("Invalid regex");
-        for token in tokens.find_iter(input).map(|m| Token::new(m.as_str())) {
-            match token {
-                // groups
-                Token::GroupOpen => operators.push_front(token),
-                Token::GroupClose => {
-                    while let Some(op) = operators.pop_front() {
-                        if op == Token::GroupOpen {
-                            break;
-                        } else {
-                            output.push(op)
-                        }
-                    }
-                }
-                // operators
-                Token::Plus
-                | Token::Minus
-                | Token::Slash
-                | Token::Star
-                | Token::Sqrt
-                | Token::Caret => {
-                    while let Some(last) = operators.front() {
-                        if last.order() >= token.order()
-                            && *last != Token::GroupOpen
-                            && *last != Token::Caret
-                        {
-                            let t = operators.pop_front().unwrap();
-                            output.push(t);
-                        } else {
-                            break;
-                        }
-                    }
-                    operators.push_front(token);
-                }
-                // operands
-                _ => output.push(token),
-            }
+    fn identifier(word: &str) -> Option<Token> {
+        static IDENTIFIER: OnceLock<Regex> = OnceLock::new();
+        let regex = IDENTIFIER
+            .get_or_init(|| Regex::new(r"^'?[a-zA-Z][a-zA-Z0-9_]*$").expect("Invalid regex"));
+        if !regex.is_match(word) {
+            return None;
         }
-
-        for operator in operators {
-            output.push(operator)
+        match word.strip_prefix('\'') {
+            Some(name) => Some(Token::Name(String::from(name))),
+            None => Some(Token::Identifier(String::from(word))),
         }
+    }
 
-        output
+    fn function(word: &str) -> Option<Token> {
+        let (name, count) = match word.split_once(':') {
+            Some((name, count)) => (name, Some(count.parse::<usize>().ok()?)),
+            None => (word, None),
+        };
+        Function::new(name).map(|function| Token::Function(function, count))
     }
 
-    /// Rules that reflect conventions about which operations to perform first in order to evaluate
-    /// a given mathematical expression.
-    /// https://en.wikipedia.org/wiki/Order_of_operations
-    pub fn order(&self) -> i8 {
-        match self {
-            Token::Number(_) | Token::Unknown(_) | Token::Undo | Token::Pop | Token::Clear => 0,
-            // addition and subtraction
-            Token::Plus | Token::Minus => 1,
-            // multiplication and division
-            Token::Star | Token::Slash => 3,
-            // exponentiation
-            Token::Caret | Token::Sqrt => 4,
-            // parenthesis
-            Token::GroupOpen | Token::GroupClose => i8::MAX,
+    /// Checks a, possibly signed and possibly imaginary, numeric literal, removing its digit
+    /// separators.
+    pub fn number(literal: &str) -> Option<String> {
+        static LITERAL: OnceLock<Regex> = OnceLock::new();
+        let regex = LITERAL.get_or_init(|| {
+            Regex::new(&format!("^[+-]?(?:{}|{}i?)$", RADIX, NUMBER)).expect("Invalid regex")
+        });
+        if !regex.is_match(literal) {
+            return None;
         }
+        Some(literal.replace('_', ""))
+    }
+
+    /// Whether the token changes the stack itself rather than computing a value.
+    pub fn is_command(&self) -> bool {
+        matches!(
+            self,
+            Token::Undo
+                | Token::Redo
+                | Token::Pop
+                | Token::Clear
+                | Token::Swap
+                | Token::Dup
+                | Token::Over
+                | Token::Rot
+                | Token::Roll
+                | Token::Pick
+                | Token::DropN
+                | Token::DupN
+                | Token::Depth
+                | Token::Reverse
+                | Token::MemoryAdd
+                | Token::MemorySubtract
+                | Token::MemoryClear
+                | Token::MemoryRecall
+                | Token::Factor
+                | Token::End
+                | Token::Eval
+                | Token::Angle(_)
+                | Token::Coordinates(_)
+                | Token::Radix(_)
+        )
+    }
+
+    /// Splits a postfix input on whitespace, keeping track of where each token came from.
+    pub fn split(input: &str) -> Vec<(Token, Span)> {
+        static WORDS: OnceLock<Regex> = OnceLock::new();
+        let regex = WORDS.get_or_init(|| Regex::new(r"\S+").expect("Invalid regex"));
+        regex
+            .find_iter(input)
+            .map(|m| (Token::new(m.as_str()), Span::from(m.range())))
+            .collect()
+    }
+
+    /// Splits an infix input into numbers, words and single symbols, which need no whitespace
+    /// in between. An `i` right after a number makes it imaginary, unless a word goes on.
+    /// The memory keys `m+` and `m-` are only read on their own, as `m+1` adds 1 to m.
+    /// Comparisons of two symbols are read before the `!` of factorials.
+    pub fn scan(input: &str) -> Vec<(Token, Span)> {
+        static TOKENS: OnceLock<Regex> = OnceLock::new();
+        let regex = TOKENS.get_or_init(|| {
+            Regex::new(&format!(
+                r"^\s*(m[+-])\s*$|{}|{}(?:i\b)?|[a-zA-Z][a-zA-Z0-9_]*|//|[<>=!]=|[^\s\t\n]",
+                RADIX, NUMBER
+            ))
+            .expect("Invalid regex")
+        });
+        regex
+            .captures_iter(input)
+            .filter_map(|c| c.get(1).or_else(|| c.get(0)))
+            .map(|m| (Token::new(m.as_str()), Span::from(m.range())))
+            .collect()
     }
 }
 
@@ -114,15 +312,63 @@ impl Display for Token {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Token::Number(n) => write!(f, "{}", n),
+            Token::Constant(c) => write!(f, "{}", c),
             Token::Plus => write!(f, "+"),
             Token::Minus => write!(f, "-"),
             Token::Slash => write!(f, "/"),
+            Token::SlashSlash => write!(f, "//"),
+            Token::Rem => write!(f, "rem"),
             Token::Star => write!(f, "*"),
             Token::Caret => write!(f, "^"),
+            Token::Bang => write!(f, "!"),
+            Token::Percent => write!(f, "%"),
+            Token::Squared => write!(f, "²"),
+            Token::Cubed => write!(f, "³"),
             Token::Sqrt => write!(f, "sqrt"),
+            Token::Negate => write!(f, "neg"),
+            Token::Function(function, None) => write!(f, "{}", function),
+            Token::Function(function, Some(count)) => write!(f, "{}:{}", function, count),
             Token::GroupOpen => write!(f, "("),
             Token::GroupClose => write!(f, ")"),
-            Token::Undo | Token::Pop | Token::Clear => write!(f, ""),
+            Token::Comma => write!(f, ","),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::Name(name) => write!(f, "'{}", name),
+            Token::Assign => write!(f, "="),
+            Token::Define => write!(f, "def"),
+            Token::Store => write!(f, "sto"),
+            Token::Recall => write!(f, "rcl"),
+            Token::MemoryAdd => write!(f, "m+"),
+            Token::MemorySubtract => write!(f, "m-"),
+            Token::MemoryClear => write!(f, "mc"),
+            Token::MemoryRecall => write!(f, "mr"),
+            Token::Angle(angle) => write!(f, "{}", angle),
+            Token::Coordinates(coordinates) => write!(f, "{}", coordinates),
+            Token::Radix(radix) => write!(f, "{}", radix),
+            Token::Factor => write!(f, "factor"),
+            Token::Swap => write!(f, "swap"),
+            Token::Dup => write!(f, "dup"),
+            Token::Over => write!(f, "over"),
+            Token::Rot => write!(f, "rot"),
+            Token::Roll => write!(f, "roll"),
+            Token::Pick => write!(f, "pick"),
+            Token::DropN => write!(f, "dropn"),
+            Token::DupN => write!(f, "dupn"),
+            Token::Depth => write!(f, "depth"),
+            Token::Reverse => write!(f, "reverse"),
+            Token::Record => write!(f, "rec"),
+            Token::End => write!(f, "end"),
+            Token::Compare(comparison) => write!(f, "{}", comparison),
+            Token::If => write!(f, "if"),
+            Token::Then => write!(f, "then"),
+            Token::Else => write!(f, "else"),
+            Token::For => write!(f, "for"),
+            Token::Next => write!(f, "next"),
+            Token::While => write!(f, "while"),
+            Token::Repeat => write!(f, "repeat"),
+            Token::ProgramOpen => write!(f, "<<"),
+            Token::ProgramClose => write!(f, ">>"),
+            Token::Eval => write!(f, "eval"),
+            Token::Undo | Token::Redo | Token::Pop | Token::Clear => write!(f, ""),
             Token::Unknown(u) => write!(f, "{}", u),
         }
     }
@@ -130,7 +376,6 @@ impl Display for Token {
 
 #[cfg(test)]
 mod tests {
-    use itertools::join;
     use rstest::*;
 
     use super::*;
@@ -140,41 +385,132 @@ mod tests {
         assert_eq!(Token::new("+"), Token::Plus);
         assert_eq!(Token::new("-"), Token::Minus);
         assert_eq!(Token::new("/"), Token::Slash);
+        assert_eq!(Token::new("//"), Token::SlashSlash);
+        assert_eq!(Token::new("rem"), Token::Rem);
         assert_eq!(Token::new("*"), Token::Star);
         assert_eq!(Token::new("^"), Token::Caret);
+        assert_eq!(Token::new("!"), Token::Bang);
+        assert_eq!(Token::new("%"), Token::Percent);
+        assert_eq!(Token::new("²"), Token::Squared);
+        assert_eq!(Token::new("³"), Token::Cubed);
         assert_eq!(Token::new("("), Token::GroupOpen);
         assert_eq!(Token::new(")"), Token::GroupClose);
+        assert_eq!(Token::new(","), Token::Comma);
         assert_eq!(Token::new("sqrt"), Token::Sqrt);
+        assert_eq!(Token::new("neg"), Token::Negate);
+        assert_eq!(Token::new("chs"), Token::Negate);
         assert_eq!(Token::new("undo"), Token::Undo);
-        assert_eq!(Token::new("xxx"), Token::Unknown("xxx".to_string()));
+        assert_eq!(Token::new("redo"), Token::Redo);
+        assert_eq!(Token::new("drop"), Token::Pop);
+        assert_eq!(Token::new("swap"), Token::Swap);
+        assert_eq!(Token::new("dropn"), Token::DropN);
+        assert_eq!(Token::new("reverse"), Token::Reverse);
+        assert_eq!(Token::new("deg"), Token::Angle(Angle::Degrees));
+        assert_eq!(Token::new("polar"), Token::Coordinates(Coordinates::Polar));
+        assert_eq!(Token::new("hex"), Token::Radix(Radix::Hex));
+        assert_eq!(Token::new("max"), Token::Function(Function::Max, None));
+        assert_eq!(Token::new("max:3"), Token::Function(Function::Max, Some(3)));
+        assert_eq!(Token::new("max:"), Token::Unknown("max:".to_string()));
+        assert_eq!(Token::new("x$x"), Token::Unknown("x$x".to_string()));
+        assert_eq!(Token::new("xxx"), Token::Identifier("xxx".to_string()));
+        assert_eq!(Token::new("'x_1"), Token::Name("x_1".to_string()));
+        assert_eq!(Token::new("sto"), Token::Store);
+        assert_eq!(Token::new("rcl"), Token::Recall);
+        assert_eq!(Token::new("m+"), Token::MemoryAdd);
+        assert_eq!(Token::new("mr"), Token::MemoryRecall);
+        assert_eq!(Token::new("def"), Token::Define);
+        assert_eq!(Token::new("rec"), Token::Record);
+        assert_eq!(Token::new("end"), Token::End);
+        assert_eq!(Token::new("<="), Token::Compare(Comparison::LessOrEqual));
+        assert_eq!(Token::new("≠"), Token::Compare(Comparison::NotEqual));
+        assert_eq!(Token::new("if"), Token::If);
+        assert_eq!(Token::new("«"), Token::ProgramOpen);
+        assert_eq!(Token::new(">>"), Token::ProgramClose);
+        assert_eq!(Token::new("eval"), Token::Eval);
     }
 
     #[test]
     fn should_parse_operands() {
-        assert_eq!(Token::new("1"), Token::Number(1.0));
-        assert_eq!(Token::new("1.2"), Token::Number(1.2));
+        assert_eq!(Token::new("1"), Token::Number("1".to_string()));
+        assert_eq!(Token::new("1_000.2"), Token::Number("1000.2".to_string()));
+        assert_eq!(Token::new("-2.5i"), Token::Number("-2.5i".to_string()));
+        assert_eq!(Token::new("i"), Token::Identifier("i".to_string()));
+        assert_eq!(Token::new("-0xff_ff"), Token::Number("-0xffff".to_string()));
+        assert_eq!(Token::new("0b102"), Token::Unknown("0b102".to_string()));
+        assert_eq!(Token::new("pi"), Token::Constant(Constant::Pi));
+        assert_eq!(Token::new("π"), Token::Constant(Constant::Pi));
     }
 
     #[rstest]
-    #[case("2", "2")]
-    #[case("2 + 2", "2 2 +")]
-    #[case("2 - 2", "2 2 -")]
-    #[case("2 * 2", "2 2 *")]
-    #[case("2 / 2", "2 2 /")]
-    #[case("2 ^ 2", "2 2 ^")]
-    #[case("2 * (3 + 5)", "2 3 5 + *")]
-    #[case("sqrt 4", "4 sqrt")]
-    #[case("3 + 4 * 2 / ( 1 - 5 ) ^ 2 ^ 3", "3 4 2 * 1 5 - 2 3 ^ ^ / +")]
-    // No spaces syntax
-    #[case("2+2", "2 2 +")]
-    #[case("2-2", "2 2 -")]
-    #[case("2*2", "2 2 *")]
-    #[case("2/2", "2 2 /")]
-    #[case("2^2", "2 2 ^")]
-    #[case("3+4 * 2 / (1-5)^2^3", "3 4 2 * 1 5 - 2 3 ^ ^ / +")]
-    fn should_shunting_yard(#[case] infix: &str, #[case] postfix: &str) {
-        let tokens = Token::shunting_yard(infix);
-        let result = join(tokens, " ");
-        assert_eq!(result, postfix);
+    #[case("1.5", Some(1.5))]
+    #[case(".5", Some(0.5))]
+    #[case("5.", Some(5.0))]
+    #[case("-2.5", Some(-2.5))]
+    #[case("2e3", Some(2000.0))]
+    #[case("2E-3", Some(0.002))]
+    #[case("1.5e+2", Some(150.0))]
+    #[case("1_000_000", Some(1000000.0))]
+    #[case("0.000_1", Some(0.0001))]
+    #[case("_", None)]
+    #[case("1__0", None)]
+    #[case("1_", None)]
+    #[case("2e", None)]
+    #[case(".", None)]
+    #[case("inf", None)]
+    #[case("NaN", None)]
+    fn should_parse_number(#[case] literal: &str, #[case] number: Option<f64>) {
+        let literal = Token::number(literal);
+        assert_eq!(literal.map(|l| l.parse::<f64>().unwrap()), number);
+    }
+
+    #[test]
+    fn should_track_spans() {
+        let tokens = Token::split("12  sqrt");
+        assert_eq!(
+            tokens[0],
+            (Token::Number("12".to_string()), Span::new(0, 2))
+        );
+        assert_eq!(tokens[1], (Token::Sqrt, Span::new(4, 8)));
+
+        let tokens = Token::scan("2*(3+5)");
+        assert_eq!(tokens[1], (Token::Star, Span::new(1, 2)));
+        assert_eq!(tokens[4], (Token::Plus, Span::new(4, 5)));
+
+        let tokens = Token::scan("3+4i*2in");
+        assert_eq!(
+            tokens[2],
+            (Token::Number("4i".to_string()), Span::new(2, 4))
+        );
+        assert_eq!(tokens[4], (Token::Number("2".to_string()), Span::new(5, 6)));
+
+        let tokens = Token::scan("7//2/3");
+        assert_eq!(tokens[1], (Token::SlashSlash, Span::new(1, 3)));
+        assert_eq!(tokens[3], (Token::Slash, Span::new(4, 5)));
+
+        let tokens = Token::scan("0xFF+0b1");
+        assert_eq!(
+            tokens[0],
+            (Token::Number("0xFF".to_string()), Span::new(0, 4))
+        );
+        assert_eq!(
+            tokens[2],
+            (Token::Number("0b1".to_string()), Span::new(5, 8))
+        );
+
+        let tokens = Token::scan("3!²+x³");
+        assert_eq!(tokens[1], (Token::Bang, Span::new(1, 2)));
+        assert_eq!(tokens[2], (Token::Squared, Span::new(2, 4)));
+        assert_eq!(tokens[5], (Token::Cubed, Span::new(6, 8)));
+
+        let tokens = Token::scan(" m+ ");
+        assert_eq!(tokens, vec![(Token::MemoryAdd, Span::new(1, 3))]);
+        let tokens = Token::scan("m+1");
+        assert_eq!(
+            tokens[0],
+            (Token::Identifier("m".to_string()), Span::new(0, 1))
+        );
+
+        let tokens = Token::scan("log10(1)");
+        assert_eq!(tokens[0], (Token::new("log10"), Span::new(0, 5)));
     }
 }
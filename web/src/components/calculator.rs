@@ -2,6 +2,9 @@ use leptos::*;
 use std::ops::Not;
 
 use log::{error, info};
+use shared::calc::error::CalcError;
+use shared::calc::context::Word;
+use shared::calc::number::Backend;
 use shared::calc::{Calc, Format};
 
 #[derive(Clone)]
@@ -13,7 +16,7 @@ enum Message {
 #[derive(Clone)]
 struct State {
     calc: Calc,
-    error: Option<String>,
+    error: Option<CalcError>,
     history: Vec<Message>,
     postfix: bool,
 }
@@ -65,8 +68,8 @@ pub fn CalculatorComponent() -> impl IntoView {
 
         if value.is_empty() { return };
 
+        let mut failed = None;
         state_writer.update(|state| {
-            let mut next = state.calc.clone();
             let format = if state.postfix {
                 info!("Using postfix");
                 Format::Postfix(value.as_str())
@@ -74,28 +77,37 @@ pub fn CalculatorComponent() -> impl IntoView {
                 info!("Using infix");
                 Format::Infix(value.as_str())
             };
-            match next.input(format) {
+            match state.calc.input(format) {
                 Ok(_) => {
-                    let eval = next
+                    let eval = state
+                        .calc
                         .eval()
                         .iter()
-                        .map(|e| e.to_string())
+                        .map(|e| state.calc.format(e))
                         .collect::<Vec<String>>()
                         .join(" ");
 
-                    state.history.push(Message::Input(next.to_string()));
+                    state.history.push(Message::Input(state.calc.to_string()));
                     state.history.push(Message::Output(eval));
                     state.error = None;
-                    state.calc = next;
                 }
                 Err(e) => {
                     error!("{}", e);
+                    failed = Some(e.span);
                     state.error = Some(e)
                 }
             }
         });
-        // Reset
-        input_element.set_value("");
+        match failed {
+            // Select the offending token so it can be fixed in place
+            Some(span) => {
+                let start = value[..span.start].encode_utf16().count() as u32;
+                let end = value[..span.end].encode_utf16().count() as u32;
+                input_element.set_selection_range(start, end).ok();
+            }
+            // Reset
+            None => input_element.set_value(""),
+        }
         input_element.focus().ok();
     };
 
@@ -111,14 +123,104 @@ pub fn CalculatorComponent() -> impl IntoView {
         }
     };
 
+    // Starts a new stack, as numbers of different backends do not mix exactly
+    let on_backend = move |e: ev::Event| {
+        if let Some(backend) = Backend::new(&event_target_value(&e)) {
+            state_writer.update(|state| {
+                state.calc = Calc::new(backend);
+                state.error = None;
+            });
+        }
+    };
+
+    let words = || {
+        [true, false].into_iter().flat_map(|signed| {
+            Word::SIZES
+                .into_iter()
+                .filter_map(move |bits| Word::new(bits, signed))
+        })
+    };
+
+    // Keeps the stack, whose values are all evaluated again with the new word
+    let on_word = move |e: ev::Event| {
+        let value = event_target_value(&e);
+        if let Some(word) = words().find(|word| word.to_string() == value) {
+            state_writer.update(|state| state.calc.set_word(word));
+        }
+    };
+
+    let is_programmer = move || state.with(|s| s.calc.backend() == Backend::Programmer);
+
+    let angle = move || state.with(|s| s.calc.angle().to_string());
+
+    let coordinates = move || state.with(|s| s.calc.coordinates().to_string());
+
+    let radix = move || state.with(|s| s.calc.radix().to_string());
+
+    // Numbered registers first, then the named ones which are the variables
+    let registers = move || {
+        state.with(|s| {
+            let numbered = s.calc.registers().iter().map(|(n, e)| (format!("R{}", n), e));
+            let named = s.calc.variables().iter().map(|(name, e)| (name.clone(), e));
+            numbered
+                .chain(named)
+                .map(|(name, e)| (name, s.calc.format(&s.calc.value(e))))
+                .collect::<Vec<(String, String)>>()
+        })
+    };
+
+    let has_registers = move || {
+        state.with(|s| !s.calc.registers().is_empty() || !s.calc.variables().is_empty())
+    };
+
+    let is_recording = move || state.with(|s| s.calc.is_recording());
+
+    let macros = move || {
+        state.with(|s| {
+            s.calc
+                .macros()
+                .iter()
+                .map(|(name, tokens)| (name.clone(), tokens.join(" ")))
+                .collect::<Vec<(String, String)>>()
+        })
+    };
+
+    let has_macros = move || state.with(|s| !s.calc.macros().is_empty());
+
     let is_invalid = move || state.with(|s| s.error.is_some());
 
     view! {
         <div id="calculator" class="row d-flex justify-content-center h-100">
             <div class="col-md-8 col-lg-6 col-xl-4">
                 <div class="card rounded shadow h-100">
-                    <header class="card-header justify-content-between align-items-center p-3 bg-black text-white rounded-top">
+                    <header class="card-header d-flex justify-content-between align-items-center p-3 bg-black text-white rounded-top">
                         <p class="mb-0 fw-bold">Calculator</p>
+                        <div class="d-flex align-items-center gap-2">
+                            <select class="form-select form-select-sm" on:change=on_backend>
+                                {Backend::ALL.into_iter().map(|backend| view! {
+                                    <option value=backend.to_string()
+                                        selected=move || state.with(|s| s.calc.backend() == backend)>
+                                        { backend.to_string() }
+                                    </option>
+                                }).collect_view()}
+                            </select>
+                            <Show when=is_programmer>
+                                <select class="form-select form-select-sm" on:change=on_word>
+                                    {words().map(|word| view! {
+                                        <option value=word.to_string()
+                                            selected=move || state.with(|s| s.calc.word() == word)>
+                                            { word.to_string() }
+                                        </option>
+                                    }).collect_view()}
+                                </select>
+                            </Show>
+                            <span class="badge text-bg-secondary text-uppercase">{ angle }</span>
+                            <span class="badge text-bg-secondary text-uppercase">{ coordinates }</span>
+                            <span class="badge text-bg-secondary text-uppercase">{ radix }</span>
+                            <Show when=is_recording>
+                                <span class="badge text-bg-danger text-uppercase">rec</span>
+                            </Show>
+                        </div>
                     </header>
                     <div class="card-body d-flex flex-wrap flex-row align-items-end w-100">
                         <div class="w-100 align-self-stretch overflow-auto">
@@ -128,11 +230,41 @@ pub fn CalculatorComponent() -> impl IntoView {
                                 }
                             }).collect_view() }
                         </div>
+                        <Show when=has_registers>
+                            <div class="w-100 d-flex flex-wrap gap-2 mb-2">
+                                {move || registers().into_iter().map(|(name, value)| view! {
+                                    <span class="badge text-bg-light border">
+                                        <span class="fw-bold">{ name }</span>" "{ value }
+                                    </span>
+                                }).collect_view()}
+                            </div>
+                        </Show>
+                        <Show when=has_macros>
+                            <div class="w-100 d-flex flex-wrap gap-2 mb-2">
+                                {move || macros().into_iter().map(|(name, tokens)| {
+                                    let on_remove = {
+                                        let name = name.clone();
+                                        move |_| state_writer.update(|state| {
+                                            state.calc.remove_macro(&name);
+                                        })
+                                    };
+                                    view! {
+                                        <span class="badge text-bg-light border">
+                                            <span class="fw-bold">{ name }</span>" "{ tokens }
+                                            <button type="button" class="btn-close btn-close-sm ms-1"
+                                                aria-label="Delete" on:click=on_remove></button>
+                                        </span>
+                                    }
+                                }).collect_view()}
+                            </div>
+                        </Show>
                         <form class="w-100" on:submit=on_submit>
                             <input class="form-control" class:is-invalid=is_invalid
                                 node_ref=input_element_ref />
                             <Show when=is_invalid>
-                                <div class="invalid-feedback">{ move || state.with(|s| s.error.clone()) }</div>
+                                <div class="invalid-feedback">{ move || state.with(|s| s.error.as_ref().map(|e| {
+                                    format!("{} (at {})", e, e.span.start + 1)
+                                })) }</div>
                             </Show>
                             <div class="row form-text">
                                 <div class="col">(Press enter)</div>